@@ -1,134 +1,478 @@
 extern crate clap;
-use self::clap::{App, Arg, ArgMatches};
+use self::clap::builder::ArgPredicate;
+use self::clap::error::ErrorKind;
+use self::clap::parser::ValueSource;
+use self::clap::{Arg, ArgAction, ArgMatches, Command};
+
+use self::clap::builder::PossibleValuesParser;
+
+use super::clockfmt::*;
+use super::todinfo::TodCalc;
+
+/// The built-in conversion subcommands: name, description,
+/// what the values are, and the kind of conversion
+///
+/// These do what the legacy flags (none, `-d`, `-m`, `-u`,
+/// `-p`) do at the top level. Each registered clock format
+/// also has a `from-NAME` subcommand.
+pub const CONVERSIONS: [(&str, &str, &str, TodCalc,); 5] = [
+    ("from-tod", "Converts hex TOD Clock values", "TOD", TodCalc::FromTod,),
+    ("from-date", "Converts Date/Time values (yyyy-mm-dd or yyyy.ddd, then time, or a Db2 TIMESTAMP)", "DATE", TodCalc::FromDateTime,),
+    ("from-pmc", "Converts hex Perpetual Minute Clock values", "PMC", TodCalc::FromPMC,),
+    ("from-unix", "Converts Unix Seconds Clock values (with any fraction, or in --unix-unit)", "SECONDS", TodCalc::FromUnix,),
+    ("from-packed", "Converts packed decimal dates, as from the TIME macro (DATE[@TIME], in hex), or zoned decimal dates (the hex of the bytes)", "PACKED", TodCalc::FromPacked,),
+];
+
+/// Modes that take the place of values given as arguments
+const NO_VALUES: [&str; 9] = ["clipboard", "infile", "filter", "replace", "watch", "interactive", "live", "binary", "smf"];
 
 /// Define and extract the command line arguments
-pub fn utargs() -> ArgMatches<'static,> {
-    App::new("untod",)
+pub fn utargs() -> ArgMatches {
+    let mut app = command();
+    let cmdl = app.get_matches_mut();
+    // The modes may come before the subcommand, so they are
+    // only known once the options have all been gathered
+    if let Some((name, sub,),) = cmdl.subcommand() {
+        let given = |x: &&str| cmdl.value_source(x,) == Some(ValueSource::CommandLine,);
+        if is_conversion(name,) && !sub.contains_id("values",) && !NO_VALUES.iter().any(given,) {
+            app.find_subcommand_mut(name,)
+                .unwrap()
+                .error(ErrorKind::MissingRequiredArgument, "values to convert are needed, or one of --input, --clipboard, --filter, --binary, --smf",)
+                .exit();
+        }
+    }
+    let arith = matches!(cmdl.subcommand_name(), Some("add",) | Some("sub",));
+    if !arith && cmdl.contains_id("interval",) && !cmdl.get_flag("watch",) && !cmdl.get_flag("live",) {
+        app.error(ErrorKind::MissingRequiredArgument, "--interval is only for --watch-clipboard or --live",).exit();
+    }
+    cmdl
+}
+
+/// The command line definition, with all its options and
+/// subcommands
+pub fn command() -> Command {
+    let mut app = Command::new("untod",)
         .version(crate_version!(),)
         .author("Brent Longborough",)
         .about("Converts among Date/Time, TOD, and PARS Perpetual Minute Clock for UTC, TAI or LORAN/IBM",)
         .before_help("untod: the Swiss Army Chainsaw for the TOD, and other, clocks",)
         .after_help("Default conversion is from hex TOD to UTC with leap-seconds",)
+        .subcommand_negates_reqs(true,)
+        .args(options(),)
         .arg(
-            Arg::with_name("lor",)
-                .display_order(3,)
-                .help("Ignore leap-seconds -- LORAN/IBM",)
-                .long("loran",)
-                .short("l",)
-                .takes_value(false,)
-                .conflicts_with("tai",),
-        )
-        .arg(
-            Arg::with_name("tai",)
-                .display_order(3,)
-                .help("Ignore leap-seconds -- TAI (International Atomic Clock)",)
-                .long("tai",)
-                .short("t",)
-                .takes_value(false,),
-        )
-        .arg(
-            Arg::with_name("ng",)
-                .display_order(5,)
-                .help("No Zulu timezone: suppress 0-offset result if others given",)
-                .long("zulu",)
-                .short("z",)
-                .takes_value(false,),
-        )
-        .arg(
-            Arg::with_name("clipboard",)
-                .display_order(2,)
-                .help("Get values for conversion from clipboard",)
-                .short("c",)
-                .long("clipboard",)
-                .conflicts_with_all(&["values","infile"])
-                .takes_value(false,),
-        )
-        .arg(
-            Arg::with_name("infile",)
-                .display_order(2,)
-                .help("Get values for conversion from a file ( - for STDIN )",)
-                .short("i",)
-                .long("input",)
-                .allow_hyphen_values(true)
-                .value_name("FILE",),
-        )
-        .arg(
-            Arg::with_name("headers",)
-                .display_order(2,)
-                .help("Display column headers",)
-                .long("headers",)
-                .takes_value(false,),
-        )
-        .arg(
-            Arg::with_name("csv",)
-                .display_order(2,)
-                .help("Output in CSV format",)
-                .long("csv",)
-                .takes_value(false,),
-        )
-        .arg(
-            Arg::with_name("pl",)
-                .display_order(4,)
-                .help("Pad Left: pad TOD with zeros on left",)
-                .long("lpad",)
-                .takes_value(false,),
-        )
-        .arg(
-            Arg::with_name("pr",)
-                .display_order(4,)
-                .conflicts_with("pl",)
-                .help("Pad Right: pad TOD with zeros on right (default is intelligent padding)",)
-                .long("rpad",)
-                .takes_value(false,),
-        )
-        .arg(
-            Arg::with_name("zl",)
-                .help("Local timezone: override local time offset ([-+]n.n)",)
-                .long("lzone",)
-                .env("UNTOD_LZONE",)
-                .value_name("OFFSET",),
-        )
-        .arg(
-            Arg::with_name("za",)
-                .help("Alternate timezone: specify additional timezone offset ([-+]n.n)",)
-                .long("azone",)
-                .env("UNTOD_AZONE",)
-                .value_name("OFFSET",),
-        )
-        .arg(
-            Arg::with_name("reverse",)
+            Arg::new("reverse",)
                 .display_order(1,)
                 .conflicts_with("pmc",)
-                .help("Convert from Date/Time values",)
+                .help("Convert from Date/Time values (as from-date)",)
                 .long("date",)
-                .short("d",)
-                .takes_value(false,),
+                .short('d',)
+                .action(ArgAction::SetTrue,),
         )
         .arg(
-            Arg::with_name("pmc",)
+            Arg::new("pmc",)
                 .display_order(1,)
-                .help("Convert from Perpetual Minute Clock (hex) values",)
-                .short("m",)
+                .help("Convert from Perpetual Minute Clock (hex) values (as from-pmc)",)
+                .short('m',)
                 .long("pmc",)
-                .takes_value(false,),
+                .action(ArgAction::SetTrue,),
         )
         .arg(
-            Arg::with_name("unix",)
+            Arg::new("unix",)
                 .conflicts_with("pmc",)
                 .conflicts_with("reverse",)
                 .display_order(1,)
-                .help("Convert from Unix Seconds Clock values",)
-                .short("u",)
+                .help("Convert from Unix Seconds Clock values (as from-unix)",)
+                .short('u',)
                 .long("unix",)
-                .takes_value(false,),
+                .action(ArgAction::SetTrue,),
         )
         .arg(
-            Arg::with_name("values",)
+            Arg::new("packed",)
+                .conflicts_with_all(["pmc", "reverse", "unix"],)
+                .display_order(1,)
+                .help("Convert from packed decimal dates, DATE[@TIME] in hex (as from-packed)",)
+                .short('p',)
+                .long("packed",)
+                .action(ArgAction::SetTrue,),
+        )
+        .arg(
+            Arg::new("values",)
                 .help("Values for conversion (if not from --input or --clipboard)",)
                 .value_name("VALUE",)
-                .required_unless_one(&["clipboard", "infile"])
-                .default_value_if("reverse", None, "NOW",)
-                .multiple(true,),
+                .required_unless_present_any(["reverse"].iter().chain(NO_VALUES.iter(),),)
+                .conflicts_with_all(["clipboard", "replace", "watch", "interactive", "live", "binary", "smf"],)
+                .default_value_if("reverse", ArgPredicate::IsPresent, Some("NOW",),)
+                .num_args(1..,),
+        );
+    for &(name, about, value, runtype,) in CONVERSIONS.iter() {
+        let values = Arg::new("values",)
+            .help("Values for conversion (if not from --input or --clipboard)",)
+            .value_name(value,)
+            .conflicts_with_all(["clipboard", "replace", "watch", "interactive", "live", "binary", "smf"],)
+            .num_args(1..,);
+        let values = match runtype {
+            TodCalc::FromDateTime => values.default_value("NOW",),
+            _ => values,
+        };
+        app = app.subcommand(Command::new(name,).about(about,).arg(values,),);
+    }
+    for x in FORMATS.iter() {
+        let values = Arg::new("values",)
+            .help("Values for conversion (if not from --input or --clipboard)",)
+            .value_name(x.name().to_uppercase(),)
+            .conflicts_with_all(["clipboard", "replace", "watch", "interactive", "live", "binary", "smf"],)
+            .num_args(1..,);
+        app = app.subcommand(Command::new(format!("from-{}", x.name()),).about(format!("Converts {}", x.about()),).arg(values,),);
+    }
+    app = app
+        .subcommand(
+            Command::new("diff",)
+                .about("Calculates the elapsed time between two TOD values",)
+                .arg(Arg::new("from",).help("Earlier TOD value",).value_name("TOD",).required(true,),)
+                .arg(Arg::new("to",).help("Later TOD value",).value_name("TOD",).required(true,),),
+        )
+        .subcommand(
+            Command::new("add",)
+                .about("Adds an interval (e.g. 1h30m, 250ms, 00:01:30) to a TOD value",)
+                .after_help("The TOD value ADD itself can be converted with from-tod add",)
+                .arg(Arg::new("tod",).help("TOD value",).value_name("TOD",).required(true,),)
+                .arg(
+                    Arg::new("interval",)
+                        .help("Interval to add",)
+                        .value_name("INTERVAL",)
+                        .allow_hyphen_values(true,)
+                        .required(true,),
+                ),
+        )
+        .subcommand(
+            Command::new("sub",)
+                .about("Subtracts an interval (e.g. 1h30m, 250ms, 00:01:30) from a TOD value",)
+                .arg(Arg::new("tod",).help("TOD value",).value_name("TOD",).required(true,),)
+                .arg(
+                    Arg::new("interval",)
+                        .help("Interval to subtract",)
+                        .value_name("INTERVAL",)
+                        .allow_hyphen_values(true,)
+                        .required(true,),
+                ),
         )
-        .get_matches()
+        .subcommand(
+            Command::new("range",)
+                .about("Converts every date/time from START to END, stepping by an interval (e.g. 1h, 15m, 1d)",)
+                .arg(Arg::new("start",).help("First date/time",).value_name("START",).required(true,),)
+                .arg(Arg::new("end",).help("Last date/time",).value_name("END",).required(true,),)
+                .arg(Arg::new("step",).help("Interval between values",).value_name("STEP",).required(true,),),
+        )
+        .subcommand(Command::new("leap",).about("Lists the leap seconds, with the TOD Clock value at which each took effect",),);
+    app
+}
+
+/// Whether a subcommand is one of the conversions
+fn is_conversion(name: &str) -> bool { CONVERSIONS.iter().any(|x| x.0 == name,) || clock_format(name,).is_some() }
+
+/// The registered format for a `from-NAME` subcommand
+fn clock_format(name: &str) -> Option<&'static dyn ClockFormat,> { name.strip_prefix("from-",).and_then(find_format,) }
+
+/// The kind of conversion: from the subcommand, if there is
+/// one, or from the legacy flags
+pub fn runtype(cmdl: &ArgMatches) -> TodCalc {
+    if let Some(name,) = cmdl.subcommand_name() {
+        if let Some(x,) = CONVERSIONS.iter().find(|x| x.0 == name,) {
+            return x.3;
+        }
+        if let Some(x,) = clock_format(name,) {
+            return TodCalc::FromClock(x,);
+        }
+    }
+    if cmdl.get_flag("pmc",) {
+        TodCalc::FromPMC
+    } else if cmdl.get_flag("packed",) {
+        TodCalc::FromPacked
+    } else if cmdl.get_flag("unix",) {
+        TodCalc::FromUnix
+    } else if cmdl.get_flag("reverse",) {
+        TodCalc::FromDateTime
+    } else {
+        TodCalc::FromTod
+    }
+}
+
+/// The values given as arguments, to the conversion
+/// subcommand or at the top level
+pub fn values(cmdl: &ArgMatches) -> Vec<String,> {
+    let matches = match cmdl.subcommand() {
+        Some((name, sub,),) if is_conversion(name,) => sub,
+        _ => cmdl,
+    };
+    match matches.get_many::<String,>("values",) {
+        Some(x,) => x.cloned().collect(),
+        None => Vec::new(),
+    }
+}
+
+/// The option value for an argument, if given
+pub fn value<'a,>(cmdl: &'a ArgMatches, name: &str,) -> Option<&'a str,> {
+    cmdl.get_one::<String,>(name,).map(String::as_str,)
+}
+
+/// The options that apply to every conversion, whether
+/// given before or after the subcommand
+fn options() -> Vec<Arg,> {
+    vec![
+        Arg::new("lor",)
+            .display_order(3,)
+            .help("Ignore leap-seconds -- LORAN/IBM",)
+            .long("loran",)
+            .short('l',)
+            .action(ArgAction::SetTrue,)
+            .conflicts_with("tai",),
+        Arg::new("tai",)
+            .display_order(3,)
+            .help("Ignore leap-seconds -- TAI (International Atomic Clock)",)
+            .long("tai",)
+            .short('t',)
+            .action(ArgAction::SetTrue,),
+        Arg::new("utc",)
+            .display_order(3,)
+            .help("Count leap-seconds -- UTC (the default), whatever the configuration says",)
+            .long("utc",)
+            .action(ArgAction::SetTrue,)
+            .conflicts_with_all(["lor", "tai"],),
+        Arg::new("ng",)
+            .display_order(5,)
+            .help("No Zulu timezone: suppress 0-offset result if others given",)
+            .long("zulu",)
+            .short('z',)
+            .action(ArgAction::SetTrue,),
+        Arg::new("zulu",)
+            .display_order(5,)
+            .help("Keep the Zulu timezone, whatever the configuration says",)
+            .long("keep-zulu",)
+            .action(ArgAction::SetTrue,)
+            .conflicts_with("ng",),
+        Arg::new("clipboard",)
+            .display_order(2,)
+            .help("Get values for conversion from clipboard",)
+            .short('c',)
+            .long("clipboard",)
+            .conflicts_with("infile",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("toclip",)
+            .display_order(2,)
+            .help("Put the results on the clipboard, as well as on STDOUT (on X11, only while untod runs, unless a clipboard manager keeps them)",)
+            .short('C',)
+            .long("to-clipboard",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("replace",)
+            .display_order(2,)
+            .help("Convert the values on the clipboard in place, leaving any other text alone",)
+            .long("replace-clipboard",)
+            .conflicts_with_all(["infile", "clipboard", "filter", "toclip"],)
+            .action(ArgAction::SetTrue,),
+        Arg::new("watch",)
+            .display_order(2,)
+            .help("Watch the clipboard, converting values as they are copied to it",)
+            .short('w',)
+            .long("watch-clipboard",)
+            .conflicts_with_all(["infile", "clipboard", "filter", "toclip", "replace"],)
+            .action(ArgAction::SetTrue,),
+        Arg::new("interval",)
+            .display_order(2,)
+            .help("With --watch-clipboard or --live: how often to look at the clipboard, or to refresh the clock (e.g. 500ms, 2s)",)
+            .long("interval",)
+            .value_name("INTERVAL",),
+        Arg::new("live",)
+            .display_order(2,)
+            .help("Show the current time, refreshed at every interval, until interrupted",)
+            .long("live",)
+            .conflicts_with_all(["infile", "clipboard", "filter", "replace", "watch", "interactive"],)
+            .action(ArgAction::SetTrue,),
+        Arg::new("interactive",)
+            .display_order(2,)
+            .help("Interactive mode: convert values as they are typed (:help for commands)",)
+            .long("interactive",)
+            .conflicts_with_all(["infile", "clipboard", "filter", "replace", "watch"],)
+            .action(ArgAction::SetTrue,),
+        Arg::new("infile",)
+            .display_order(2,)
+            .help("Get values for conversion from a file ( - for STDIN ); may be repeated",)
+            .short('i',)
+            .long("input",)
+            .allow_hyphen_values(true,)
+            .action(ArgAction::Append,)
+            .value_name("FILE",),
+        Arg::new("filter",)
+            .display_order(2,)
+            .help("Filter mode: copy text input, annotating the values found in it",)
+            .short('f',)
+            .long("filter",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("pattern",)
+            .display_order(2,)
+            .help("Filter mode: regular expression for the values (capture group 1, if any, is converted)",)
+            .long("pattern",)
+            .requires("filter",)
+            .value_name("REGEX",),
+        Arg::new("minlen",)
+            .display_order(2,)
+            .help("Filter mode: ignore values shorter than this",)
+            .long("min-len",)
+            .requires("filter",)
+            .value_name("LENGTH",),
+        Arg::new("fstyle",)
+            .display_order(2,)
+            .help("Filter mode: put converted values after each value, in its place, or at the end of the line",)
+            .long("filter-style",)
+            .requires("filter",)
+            .value_parser(["after", "replace", "end"],)
+            .value_name("STYLE",),
+        Arg::new("binary",)
+            .display_order(2,)
+            .help("Binary mode: convert a raw STCK or STCKE field in each fixed-length record of the input",)
+            .short('b',)
+            .long("binary",)
+            .conflicts_with_all(["clipboard", "filter", "replace", "watch", "interactive", "live"],)
+            .action(ArgAction::SetTrue,),
+        Arg::new("smf",)
+            .display_order(2,)
+            .help("SMF mode: convert the header date/time of each record of an SMF dump (with RDWs), and the field at --field-offset, if given",)
+            .long("smf",)
+            .conflicts_with_all(["clipboard", "filter", "replace", "watch", "interactive", "live", "binary"],)
+            .action(ArgAction::SetTrue,),
+        Arg::new("reclen",)
+            .display_order(2,)
+            .help("Binary mode: length of each record (default: just the field)",)
+            .long("record-length",)
+            .requires("binary",)
+            .value_name("LENGTH",),
+        Arg::new("foffset",)
+            .display_order(2,)
+            .help("Binary and SMF modes: offset of the field in each record (default 0)",)
+            .long("field-offset",)
+            .value_name("OFFSET",),
+        Arg::new("fwidth",)
+            .display_order(2,)
+            .help("Binary and SMF modes: width of the field, 8 for STCK or 16 for STCKE; 4, 8 or 16 for packed decimal; 7 or 8 for zoned decimal; 8 for ABSTIME; 6 or 10 for an LRSN; 2 or 4 for z/TPF clocks; 8 for FILETIME, .NET ticks, Java millis, VMS time, Excel (a double) and NTP; 10 for PTP (default 8)",)
+            .long("field-width",)
+            .value_parser(["2", "4", "6", "7", "8", "10", "16"],)
+            .value_name("WIDTH",),
+        Arg::new("pformat",)
+            .display_order(2,)
+            .help("Packed decimal date layout (default auto)",)
+            .long("packed-format",)
+            .value_parser(["auto", "cyyddd", "yyyyddd", "yyyymmdd", "mmddyyyy", "ddmmyyyy"],)
+            .value_name("LAYOUT",),
+        Arg::new("encoding",)
+            .display_order(2,)
+            .help("Encoding of input files and STDIN, and of the results (default utf-8); not for --interactive or --live, which are for a terminal",)
+            .long("encoding",)
+            .conflicts_with_all(["interactive", "live"],)
+            .value_parser(["utf-8", "ebcdic-037", "ebcdic-1047"],)
+            .value_name("ENCODING",),
+        Arg::new("uunit",)
+            .display_order(2,)
+            .help("Unit of Unix time values to convert: s, ms, us or ns (default auto: by the number of digits)",)
+            .long("unix-unit",)
+            .value_parser(["auto", "s", "ms", "us", "ns"],)
+            .value_name("UNIT",),
+        Arg::new("uout",)
+            .display_order(2,)
+            .help("Unit of Unix time results: s (with the fraction), ms, us or ns (default auto: seconds, with any fraction)",)
+            .long("unix-out",)
+            .value_parser(["auto", "s", "ms", "us", "ns"],)
+            .value_name("UNIT",),
+        Arg::new("unixms",)
+            .display_order(2,)
+            .help("Unix time in milliseconds, in and out (as --unix-unit ms --unix-out ms)",)
+            .long("unix-ms",)
+            .conflicts_with_all(["uunit", "uout"],)
+            .action(ArgAction::SetTrue,),
+        Arg::new("columns",)
+            .display_order(2,)
+            .help(format!("Add optional columns to the results (comma-separated): {}", format_names().join(", ")),)
+            .long("column",)
+            .value_parser(PossibleValuesParser::new(format_names(),),)
+            .value_delimiter(',',)
+            .action(ArgAction::Append,)
+            .value_name("COLUMN",),
+        Arg::new("excel1904",)
+            .display_order(2,)
+            .help("Excel serial dates are in the 1904 date system",)
+            .long("excel-1904",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("strict",)
+            .display_order(2,)
+            .help("Stop at the first value that can't be converted",)
+            .long("strict",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("summary",)
+            .display_order(2,)
+            .help("Report the numbers of values converted and failed on STDERR",)
+            .long("summary",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("headers",)
+            .display_order(2,)
+            .help("Display column headers",)
+            .long("headers",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("csv",)
+            .display_order(2,)
+            .help("Output in CSV format",)
+            .long("csv",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("text",)
+            .display_order(2,)
+            .help("Output as text (the default), whatever the configuration says",)
+            .long("text",)
+            .action(ArgAction::SetTrue,)
+            .conflicts_with("csv",),
+        Arg::new("pl",)
+            .display_order(4,)
+            .help("Pad Left: pad TOD with zeros on left",)
+            .long("lpad",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("pr",)
+            .display_order(4,)
+            .conflicts_with("pl",)
+            .help("Pad Right: pad TOD with zeros on right (default is intelligent padding)",)
+            .long("rpad",)
+            .action(ArgAction::SetTrue,),
+        Arg::new("zl",)
+            .help("Local timezone: override local time offset ([-+]n.n)",)
+            .long("lzone",)
+            .env("UNTOD_LZONE",)
+            .allow_hyphen_values(true,)
+            .value_name("OFFSET",),
+        Arg::new("za",)
+            .help("Alternate timezone: specify additional timezone offset ([-+]n.n)",)
+            .long("azone",)
+            .env("UNTOD_AZONE",)
+            .allow_hyphen_values(true,)
+            .value_name("OFFSET",),
+        Arg::new("config",)
+            .help("Read defaults from this configuration file (default ~/.config/untod/config.toml)",)
+            .long("config",)
+            .env("UNTOD_CONFIG",)
+            .value_name("FILE",),
+        Arg::new("profile",)
+            .help("Use this profile from the configuration file",)
+            .long("profile",)
+            .env("UNTOD_PROFILE",)
+            .value_name("NAME",),
+        Arg::new("template",)
+            .help(format!(
+                "Output template, with fields {{tod}} {{date}} {{time}} {{zone}} {{julian}} {{day}} {{pmc}} {{unix}} {{leap}} {}",
+                format_names().iter().map(|x| format!("{{{}}}", x),).collect::<Vec<String,>>().join(" ",)
+            ),)
+            .long("template",)
+            .value_name("TEMPLATE",),
+        Arg::new("notemplate",)
+            .help("Ignore the template from the configuration",)
+            .long("no-template",)
+            .action(ArgAction::SetTrue,)
+            .conflicts_with("template",),
+    ]
+    .into_iter()
+    .map(|x| x.global(true,),)
+    .collect()
 }
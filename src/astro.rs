@@ -0,0 +1,179 @@
+extern crate chrono;
+use self::chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use super::clockfmt::*;
+use super::todinfo::*;
+
+/// TT - TAI, in microseconds
+const TT_TAI: i64 = 32_184_000;
+
+/// Finds the nanoseconds in a decimal number of days, with
+/// or without a fraction
+pub fn day_nanos(text: &str) -> Option<i128,> {
+    let (negative, text,) = match text.strip_prefix('-',) {
+        Some(x,) => (true, x,),
+        None => (false, text,),
+    };
+    let mut parts = text.splitn(2, '.',);
+    let whole = parts.next()?;
+    let fraction = parts.next().unwrap_or("",);
+    if whole.is_empty() || !whole.bytes().chain(fraction.bytes(),).all(|b| b.is_ascii_digit(),) {
+        return None;
+    }
+    let fraction: i128 = format!("{:0<15}", fraction)[..15].parse().ok()?;
+    let n = whole.parse::<i128>().ok()?.checked_mul(DAY,)? + fraction * DAY / 1_000_000_000_000_000;
+    Some(if negative { -n } else { n },)
+}
+
+/// Defines the astronomers' day counts, in days of 86,400
+/// seconds with a fraction, counted here in nanoseconds:
+/// *   Jd: Julian Day (the usual *Julian* column is the
+///     ordinal date)
+/// *   Mjd: Modified Julian Day, JD - 2400000.5
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DayCount {
+    Jd,
+    Mjd,
+}
+
+impl ClockFormat for DayCount {
+    fn name(&self) -> &'static str {
+        match *self {
+            DayCount::Jd => "jd",
+            DayCount::Mjd => "mjd",
+        }
+    }
+
+    fn about(&self) -> &'static str {
+        match *self {
+            DayCount::Jd => "Julian Days, with any fraction",
+            DayCount::Mjd => "Modified Julian Days, with any fraction",
+        }
+    }
+
+    fn heading(&self) -> &'static str {
+        match *self {
+            DayCount::Jd => "JD",
+            DayCount::Mjd => "MJD",
+        }
+    }
+
+    fn width(&self) -> usize {
+        match *self {
+            DayCount::Jd => 16,
+            DayCount::Mjd => 14,
+        }
+    }
+
+    /// Noon on 4713 BC January 1 (Julian calendar) for the
+    /// JD, as a proleptic Gregorian date
+    fn epoch(&self) -> NaiveDateTime {
+        match *self {
+            DayCount::Jd => NaiveDate::from_ymd(-4713, 11, 24,).and_hms(12, 0, 0,),
+            DayCount::Mjd => NaiveDate::from_ymd(1858, 11, 17,).and_hms(0, 0, 0,),
+        }
+    }
+
+    fn resolution(&self) -> i128 { 1 }
+
+    fn range(&self) -> (i128, i128,) { (i128::MIN, i128::MAX,) }
+
+    fn parse(&self, text: &str,) -> Option<i128,> { day_nanos(text,) }
+
+    fn format(&self, count: i128,) -> String { format!("{:.8}", count as f64 / DAY as f64) }
+
+    /// Counted in UTC days, whatever the zone shown
+    fn absolute(&self) -> bool { true }
+
+    fn pattern(&self) -> &'static str {
+        match *self {
+            DayCount::Jd => r"\b24[0-9]{5}(?:\.[0-9]+)?\b",
+            DayCount::Mjd => r"\b[0-9]{5}(?:\.[0-9]+)?\b",
+        }
+    }
+}
+
+/// Defines the dynamical time scales, as ISO 8601 dates and
+/// times, counted here in nanoseconds from 1900:
+/// *   Tt: Terrestrial Time, TAI + 32.184 seconds
+/// *   Tdb: Barycentric Dynamical Time, from TT with the
+///     usual approximation (good to about 30 microseconds)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DynamicalTime {
+    Tt,
+    Tdb,
+}
+
+/// Calculates TDB - TT for a TT date and time
+fn tdb_tt(tt: NaiveDateTime) -> Duration {
+    let j2000 = NaiveDate::from_ymd(2000, 1, 1,).and_hms(12, 0, 0,);
+    let days = tt.signed_duration_since(j2000,).num_microseconds().unwrap_or(0,) as f64 / 86_400_000_000.0;
+    let g = (357.53 + 0.985_600_28 * days).to_radians();
+    let seconds = 0.001_657 * g.sin() + 0.000_014 * (2.0 * g).sin();
+    Duration::microseconds((seconds * 1_000_000.0).round() as i64,)
+}
+
+impl ClockFormat for DynamicalTime {
+    fn name(&self) -> &'static str {
+        match *self {
+            DynamicalTime::Tt => "tt",
+            DynamicalTime::Tdb => "tdb",
+        }
+    }
+
+    fn about(&self) -> &'static str {
+        match *self {
+            DynamicalTime::Tt => "Terrestrial Time (yyyy-mm-ddThh:mm:ss.nnnnnn)",
+            DynamicalTime::Tdb => "Barycentric Dynamical Time (yyyy-mm-ddThh:mm:ss.nnnnnn)",
+        }
+    }
+
+    fn heading(&self) -> &'static str {
+        match *self {
+            DynamicalTime::Tt => "TT",
+            DynamicalTime::Tdb => "TDB",
+        }
+    }
+
+    fn width(&self) -> usize { 26 }
+
+    fn epoch(&self) -> NaiveDateTime { NaiveDate::from_ymd(1900, 1, 1,).and_hms(0, 0, 0,) }
+
+    fn resolution(&self) -> i128 { 1 }
+
+    fn range(&self) -> (i128, i128,) { (i128::MIN, i128::MAX,) }
+
+    fn parse(&self, text: &str,) -> Option<i128,> {
+        let date = NaiveDateTime::parse_from_str(text, "%FT%H:%M:%S%.f",).ok()?;
+        nanos_since(date, self.epoch(),)
+    }
+
+    fn format(&self, count: i128,) -> String {
+        match add_nanos(self.epoch(), count,) {
+            Some(x,) => x.format("%FT%H:%M:%S%.6f",).to_string(),
+            None => "-".repeat(26,),
+        }
+    }
+
+    /// Takes off the TDB - TT difference, then the leap
+    /// seconds in effect
+    fn to_instant(&self, count: i128, todwork: &TodInfo,) -> Option<NaiveDateTime,> {
+        let mut tt = add_nanos(self.epoch(), count,)?;
+        if *self == DynamicalTime::Tdb {
+            tt = tt - tdb_tt(tt,);
+        }
+        Some(todwork.lstab.tai_to_utc(tt - Duration::microseconds(TT_TAI,),),)
+    }
+
+    fn to_count(&self, date: NaiveDateTime, todwork: &TodInfo,) -> Option<i128,> {
+        let mut tt = todwork.lstab.utc_to_tai(date,) + Duration::microseconds(TT_TAI,);
+        if *self == DynamicalTime::Tdb {
+            tt = tt + tdb_tt(tt,);
+        }
+        nanos_since(tt, self.epoch(),)
+    }
+
+    fn absolute(&self) -> bool { true }
+
+    fn pattern(&self) -> &'static str { r"\b[0-9]{4}-[0-9]{2}-[0-9]{2}T[0-9]{2}:[0-9]{2}:[0-9]{2}(?:\.[0-9]+)?" }
+}
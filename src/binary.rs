@@ -0,0 +1,344 @@
+extern crate chrono;
+use self::chrono::{Duration, NaiveDateTime};
+
+extern crate clap;
+use self::clap::ArgMatches;
+
+use super::args::value;
+use super::clockfmt::*;
+use super::input::*;
+use super::packed::*;
+use super::todinfo::*;
+
+use std::io::{self, BufRead, Read};
+use std::vec;
+
+/// Where the TOD Clock field is in each binary record
+/// *   reclen: Length of every record
+/// *   offset: Offset of the field in the record
+/// *   width: 8 for STCK, 16 for STCKE
+#[derive(Clone, Copy, Debug)]
+pub struct BinaryField {
+    pub reclen: usize,
+    pub offset: usize,
+    pub width:  usize,
+}
+
+impl BinaryField {
+    /// Builds the field description from command line
+    /// arguments
+    ///
+    /// Without a record length, the records are just the
+    /// field, after the offset.
+    pub fn new_from_args(cmdl: &ArgMatches) -> Result<BinaryField, String,> {
+        let number = |name: &str, opt: &str, default: usize| match value(cmdl, name,) {
+            None => Ok(default,),
+            Some(x,) => x.parse::<usize>().map_err(|_| format!("Invalid number: {} {}", opt, x),),
+        };
+        let offset = number("foffset", "--field-offset", 0,)?;
+        let width = number("fwidth", "--field-width", 8,)?;
+        let reclen = number("reclen", "--record-length", offset + width,)?;
+        if offset + width > reclen {
+            return Err(format!("The field (offset {}, width {}) doesn't fit in a {}-byte record", offset, width, reclen),);
+        }
+        Ok(BinaryField { reclen, offset, width, },)
+    }
+
+    /// Converts the field in a record: a TOD Clock value or,
+    /// when converting from packed decimal or a registered
+    /// clock format, a value of that kind
+    pub fn convert(&self, record: &[u8], todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+        if record.len() < self.offset + self.width {
+            return Err(format!("Record is too short for the field: {} bytes", record.len()),);
+        }
+        let raw = &record[self.offset..self.offset + self.width];
+        match todwork.runtype {
+            TodCalc::FromPacked => {
+                todwork.date = match packed_bytes(raw, todwork.packed,).or_else(|| zoned_bytes(raw, todwork.packed,),) {
+                    None => return Err(format!("Packed or zoned date is invalid: {:02x?}", raw),),
+                    Some(x,) => x,
+                };
+                return from_datetime_value(todwork,);
+            },
+            TodCalc::FromClock(x,) => {
+                return match x.count_from_bytes(raw,) {
+                    None => Err(format!("{} value is invalid, or the field width ({}) is wrong for it: {:02x?}", x.heading(), self.width, raw),),
+                    Some(count,) => from_clock_count(count, x, todwork,),
+                };
+            },
+            _ => {},
+        }
+        match Tod::new_from_bytes(raw,) {
+            None => Err(format!("Field width must be 8 or 16 for a TOD Clock, not {}", self.width),),
+            Some(x,) => {
+                todwork.tod = x;
+                from_tod_value(todwork,)
+            },
+        }
+    }
+}
+
+/// How the input is divided into records
+/// *   Fixed: Every record has the same length
+/// *   Rdw: Each record starts with a Record Descriptor
+///     Word, giving its length, as in an SMF dump; spanned
+///     records are put back together
+#[derive(Clone, Copy, Debug)]
+pub enum Framing {
+    Fixed(usize,),
+    Rdw,
+}
+
+impl Framing {
+    /// Reads the next record, or *None* at the end of the
+    /// input
+    ///
+    /// A record read with its RDW keeps it, so that offsets
+    /// are as in the record layouts. A spanned record is
+    /// given a new RDW for its whole length.
+    fn read(&self, rdr: &mut dyn BufRead,) -> io::Result<Option<Vec<u8,>,>,> {
+        match *self {
+            Framing::Fixed(reclen,) => Ok(read_bytes(rdr, reclen,)?,),
+            Framing::Rdw => {
+                let mut record: Vec<u8,> = Vec::new();
+                loop {
+                    let rdw = match read_bytes(rdr, 4,)? {
+                        Some(x,) => x,
+                        None if record.is_empty() => return Ok(None,),
+                        None => return Err(partial("ends in the middle of a spanned record",),),
+                    };
+                    let len = usize::from(u16::from_be_bytes([rdw[0], rdw[1]],),);
+                    if len < 4 {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("has an invalid RDW: {:02x?}", rdw),),);
+                    }
+                    let data = match read_bytes(rdr, len - 4,)? {
+                        Some(x,) => x,
+                        None => return Err(partial("ends part way through a record",),),
+                    };
+                    // Segment: 0 whole, 1 first, 2 last, 3 middle
+                    let segment = rdw[2] & 0x03;
+                    if record.is_empty() {
+                        record.extend_from_slice(&rdw,);
+                    }
+                    record.extend_from_slice(&data,);
+                    if segment == 0 || segment == 2 {
+                        break;
+                    }
+                }
+                let len = record.len().min(0xffff,) as u16;
+                record[0..2].copy_from_slice(&len.to_be_bytes(),);
+                record[2] = 0;
+                Ok(Some(record,),)
+            },
+        }
+    }
+}
+
+/// Reads exactly so many bytes, or *None* at the end of the
+/// input
+fn read_bytes(rdr: &mut dyn BufRead, len: usize,) -> io::Result<Option<Vec<u8,>,>,> {
+    let mut buffer: Vec<u8,> = Vec::with_capacity(len,);
+    match rdr.take(len as u64,).read_to_end(&mut buffer,)? {
+        0 if len > 0 => Ok(None,),
+        n if n == len => Ok(Some(buffer,),),
+        n => Err(partial(&format!("ends with a partial record of {} bytes", n),),),
+    }
+}
+
+/// An error for input that stops part way through
+fn partial(what: &str) -> io::Error { io::Error::new(io::ErrorKind::UnexpectedEof, what,) }
+
+/// Binary records from a list of files ( - for STDIN ),
+/// read in order
+///
+/// As with lines of text, a file that can't be opened or
+/// read is reported in place of its records. A file that
+/// ends part way through a record is reported as a read
+/// error.
+pub struct Records {
+    files:   vec::IntoIter<String,>,
+    current: Option<(String, Box<dyn BufRead,>,),>,
+    framing: Framing,
+}
+
+impl Records {
+    /// Makes a new set of records from a list of file names
+    pub fn new(files: Vec<String,>, framing: Framing,) -> Records {
+        Records {
+            files: files.into_iter(),
+            current: None,
+            framing,
+        }
+    }
+}
+
+impl Iterator for Records {
+    type Item = Result<Vec<u8,>, InputError,>;
+
+    fn next(&mut self) -> Option<Self::Item,> {
+        loop {
+            if let Some((ref name, ref mut rdr,),) = self.current {
+                match self.framing.read(rdr,) {
+                    Ok(None,) => {},
+                    Ok(Some(x,),) => return Some(Ok(x,),),
+                    Err(e,) => {
+                        let name = name.clone();
+                        self.current = None;
+                        return Some(Err(InputError::Read(name, e,),),);
+                    },
+                }
+            }
+            self.current = None;
+            let name = self.files.next()?;
+            match open_input(&name,) {
+                Ok(x,) => self.current = Some((name, x,),),
+                Err(e,) => return Some(Err(e,),),
+            }
+        }
+    }
+}
+
+/// Converts the date and time in the header of an SMF
+/// record, as a date/time value
+pub fn smf_header(record: &[u8], todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    todwork.date = smf_datetime(record,)?;
+    from_datetime_value(todwork,)
+}
+
+/// Finds the date and time in the header of an SMF record
+/// (with its RDW)
+///
+/// `SMFxTME`, at offset 6, is hundredths of a second since
+/// midnight; `SMFxDTE`, at offset 10, is packed decimal
+/// *0cyydddF*, where *c* is 0 for 19xx and 1 for 20xx.
+/// Both are the system's local time.
+pub fn smf_datetime(record: &[u8]) -> Result<NaiveDateTime, String,> {
+    if record.len() < 14 {
+        return Err(format!("SMF record is too short for a header: {} bytes", record.len()),);
+    }
+    let tme = u32::from_be_bytes([record[6], record[7], record[8], record[9]],);
+    let dte = &record[10..14];
+    let hex = hex_string(dte,);
+    let date = packed_date(&hex, PackedFormat::Cyyddd,);
+    match date {
+        Some(x,) if tme < 8_640_000 => Ok(x.and_hms(0, 0, 0,) + Duration::milliseconds(i64::from(tme,) * 10,),),
+        _ => Err(format!("SMF record has an invalid date/time: {:02x?} {:08x}", dte, tme),),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+
+    /// 2017-01-01 00:00:00 UTC as a STCK value
+    const STCK: [u8; 8] = [0xd1, 0xe0, 0xd6, 0x81, 0x73, 0xcc, 0x00, 0x00];
+
+    /// Writes a fixture file, giving its name
+    fn fixture(name: &str, bytes: &[u8],) -> String {
+        let path = env::temp_dir().join(format!("untod-{}-{}", std::process::id(), name),);
+        fs::write(&path, bytes,).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn fixed_records_across_files() {
+        let one = fixture("fixed1", &[STCK, STCK].concat(),);
+        let two = fixture("fixed2", &STCK,);
+        let records: Vec<Vec<u8,>,> = Records::new(vec![one.clone(), two.clone()], Framing::Fixed(8,),).map(Result::unwrap,).collect();
+        fs::remove_file(one,).unwrap();
+        fs::remove_file(two,).unwrap();
+        assert_eq!(records, vec![STCK.to_vec(); 3]);
+        let field = BinaryField { reclen: 8, offset: 0, width: 8, };
+        let x = field.convert(&records[0], &mut TodInfo::new(),).unwrap();
+        assert!(x[0].contains("2017-01-01 00:00:00.000000",), "{:?}", x);
+    }
+
+    #[test]
+    fn short_last_record() {
+        let name = fixture("short", &[&STCK[..], &STCK[..4]].concat(),);
+        let mut records = Records::new(vec![name.clone()], Framing::Fixed(8,),);
+        assert_eq!(records.next().unwrap().unwrap(), STCK.to_vec());
+        let e = records.next().unwrap().unwrap_err();
+        assert_eq!(e.to_string(), format!("Can't read {}: ends with a partial record of 4 bytes", name));
+        assert!(records.next().is_none());
+        fs::remove_file(name,).unwrap();
+    }
+
+    #[test]
+    fn field_offset_in_the_record() {
+        let record = [&[0u8; 4][..], &STCK[..]].concat();
+        let field = BinaryField { reclen: 12, offset: 4, width: 8, };
+        assert!(field.convert(&record, &mut TodInfo::new(),).unwrap()[0].contains("2017-01-01",));
+        let field = BinaryField { reclen: 16, offset: 8, width: 8, };
+        assert_eq!(field.convert(&record, &mut TodInfo::new(),), Err("Record is too short for the field: 12 bytes".to_string()));
+        let field = BinaryField { reclen: 12, offset: 4, width: 4, };
+        assert_eq!(field.convert(&record, &mut TodInfo::new(),), Err("Field width must be 8 or 16 for a TOD Clock, not 4".to_string()));
+    }
+
+    #[test]
+    fn missing_file() {
+        let mut records = Records::new(vec!["/nonexistent/untod".to_string()], Framing::Fixed(8,),);
+        assert!(records.next().unwrap().unwrap_err().to_string().starts_with("Can't open /nonexistent/untod: ",));
+        assert!(records.next().is_none());
+    }
+
+    /// Reads every RDW record from some bytes
+    fn rdw_records(bytes: &[u8],) -> io::Result<Vec<Vec<u8,>,>,> {
+        let mut rdr = Cursor::new(bytes,);
+        let mut records = Vec::new();
+        while let Some(x,) = Framing::Rdw.read(&mut rdr,)? {
+            records.push(x,);
+        }
+        Ok(records,)
+    }
+
+    #[test]
+    fn whole_rdw_records() {
+        let bytes = [&[0, 6, 0, 0, 1, 2][..], &[0, 5, 0, 0, 3][..]].concat();
+        assert_eq!(rdw_records(&bytes,).unwrap(), vec![vec![0, 6, 0, 0, 1, 2], vec![0, 5, 0, 0, 3]]);
+        assert!(rdw_records(&[],).unwrap().is_empty());
+    }
+
+    #[test]
+    fn spanned_rdw_records() {
+        // First, middle and last segments, then a whole record
+        let bytes = [&[0, 6, 1, 0, 1, 2][..], &[0, 5, 3, 0, 3][..], &[0, 6, 2, 0, 4, 5][..], &[0, 5, 0, 0, 6][..]].concat();
+        assert_eq!(rdw_records(&bytes,).unwrap(), vec![vec![0, 9, 0, 0, 1, 2, 3, 4, 5], vec![0, 5, 0, 0, 6]]);
+        let e = rdw_records(&bytes[..11],).unwrap_err();
+        assert_eq!((e.kind(), e.to_string()), (io::ErrorKind::UnexpectedEof, "ends in the middle of a spanned record".to_string()));
+    }
+
+    #[test]
+    fn truncated_rdw_records() {
+        let e = rdw_records(&[0, 6, 0, 0, 1, 2, 0, 5],).unwrap_err();
+        assert_eq!(e.to_string(), "ends with a partial record of 2 bytes");
+        let e = rdw_records(&[0, 8, 0, 0, 1, 2],).unwrap_err();
+        assert_eq!(e.to_string(), "ends with a partial record of 2 bytes");
+        let e = rdw_records(&[0, 3, 0, 0],).unwrap_err();
+        assert_eq!((e.kind(), e.to_string()), (io::ErrorKind::InvalidData, "has an invalid RDW: [00, 03, 00, 00]".to_string()));
+    }
+
+    #[test]
+    fn smf_header_date_and_time() {
+        // RDW, flag, type, SMFxTME 12:34:56.78, SMFxDTE 2017.001
+        let mut record = vec![0, 14, 0, 0, 0x5e, 30];
+        record.extend_from_slice(&4_529_678u32.to_be_bytes(),);
+        record.extend_from_slice(&[0x01, 0x17, 0x00, 0x1f],);
+        assert_eq!(smf_datetime(&record,).unwrap().to_string(), "2017-01-01 12:34:56.780");
+        let mut todwork = TodInfo::new();
+        todwork.set_zones(true, Toffset(None,), Toffset(None,),);
+        let x = smf_header(&record, &mut todwork,).unwrap();
+        assert_eq!(x.len(), 1);
+        assert!(x[0].contains("2017-01-01 12:34:56.780000",), "{:?}", x);
+        record[13] = 0x0c;
+        assert!(smf_datetime(&record,).unwrap_err().starts_with("SMF record has an invalid date/time",));
+        record[6..10].copy_from_slice(&8_640_000u32.to_be_bytes(),);
+        record[13] = 0x1f;
+        assert!(smf_datetime(&record,).is_err());
+        assert_eq!(smf_datetime(&record[..13],), Err("SMF record is too short for a header: 13 bytes".to_string()));
+    }
+}
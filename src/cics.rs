@@ -0,0 +1,57 @@
+extern crate chrono;
+use self::chrono::{NaiveDate, NaiveDateTime};
+
+use super::clockfmt::*;
+use super::packed::unpack;
+
+/// CICS ABSTIME: milliseconds since 1900-01-01, as from
+/// `ASKTIME ABSTIME`, in packed decimal
+///
+/// CICS counts every day as 86,400 seconds, so there are no
+/// leap seconds to allow for: the date and time are those a
+/// transaction would have seen.
+pub struct Abstime;
+
+impl ClockFormat for Abstime {
+    fn name(&self) -> &'static str { "abstime" }
+
+    fn about(&self) -> &'static str { "CICS ABSTIME values (packed decimal in hex, or decimal milliseconds since 1900)" }
+
+    fn heading(&self) -> &'static str { "ABSTIME" }
+
+    fn width(&self) -> usize { 15 }
+
+    fn epoch(&self) -> NaiveDateTime { NaiveDate::from_ymd(1900, 1, 1,).and_hms(0, 0, 0,) }
+
+    fn resolution(&self) -> i128 { 1_000_000 }
+
+    fn range(&self) -> (i128, i128,) { (0, 999_999_999_999_999,) }
+
+    /// Reads the 16 hex digits of the packed decimal form, as
+    /// in a dump, or a plain decimal number
+    fn parse(&self, text: &str,) -> Option<i128,> {
+        if text.is_empty() || !text.bytes().all(|b| b.is_ascii_hexdigit(),) {
+            return None;
+        }
+        if text.bytes().all(|b| b.is_ascii_digit(),) {
+            return text.parse::<i128>().ok();
+        }
+        if text.len() != 16 {
+            return None;
+        }
+        let raw: Vec<u8,> = (0..8).filter_map(|i| u8::from_str_radix(&text[i * 2..i * 2 + 2], 16,).ok(),).collect();
+        self.count_from_bytes(&raw,)
+    }
+
+    fn format(&self, count: i128,) -> String { format!("{:015}", count) }
+
+    fn pattern(&self) -> &'static str { r"\b[0-9]{15}[FfCc]\b" }
+
+    /// Reads the 8-byte packed decimal field
+    fn count_from_bytes(&self, raw: &[u8],) -> Option<i128,> {
+        if raw.len() != 8 {
+            return None;
+        }
+        unpack(raw,).map(i128::from,)
+    }
+}
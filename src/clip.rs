@@ -0,0 +1,170 @@
+extern crate clipboard;
+use self::clipboard::{ClipboardContext, ClipboardProvider};
+
+use super::input::InputError;
+use super::todinfo::*;
+
+use std::error::Error;
+
+/// An in-memory clipboard
+///
+/// Stands in for the system clipboard where there isn't one
+/// (or in testing)
+#[derive(Clone, Debug, Default)]
+pub struct MemClipboard(pub String,);
+impl ClipboardProvider for MemClipboard {
+    fn new() -> Result<MemClipboard, Box<dyn Error,>,> { Ok(MemClipboard::default(),) }
+
+    fn get_contents(&mut self) -> Result<String, Box<dyn Error,>,> { Ok(self.0.clone(),) }
+
+    fn set_contents(&mut self, text: String,) -> Result<(), Box<dyn Error,>,> {
+        self.0 = text;
+        Ok((),)
+    }
+}
+
+/// Opens the system clipboard
+pub fn open_clipboard() -> Result<ClipboardContext, InputError,> {
+    ClipboardContext::new().map_err(|e| InputError::Clipboard(e.to_string(),),)
+}
+
+/// Gets the contents of a clipboard
+pub fn get_clipboard<P: ClipboardProvider,>(ctx: &mut P,) -> Result<String, InputError,> {
+    ctx.get_contents().map_err(|e| InputError::Clipboard(e.to_string(),),)
+}
+
+/// Replaces the contents of a clipboard
+pub fn put_clipboard<P: ClipboardProvider,>(ctx: &mut P, text: String,) -> Result<(), InputError,> {
+    ctx.set_contents(text,).map_err(|e| InputError::Clipboard(e.to_string(),),)
+}
+
+/// Puts lines of results on a clipboard, one to a line
+///
+/// On X11 the clipboard is served by the program that set
+/// it, so the results stay there only while `untod` runs,
+/// unless a clipboard manager takes them over.
+pub fn copy_lines<P: ClipboardProvider,>(ctx: &mut P, lines: &[String],) -> Result<(), InputError,> {
+    let mut text = lines.join("\n",);
+    text.push('\n',);
+    put_clipboard(ctx, text,)
+}
+
+/// Rewrites each (whitespace-separated) value in some text
+/// with its converted form, keeping the spacing
+///
+/// Values that can't be converted are left alone. Gives the
+/// new text, and the number of values converted.
+pub fn replace_tokens(text: &str, todwork: &mut TodInfo,) -> (String, usize,) {
+    let mut result = String::with_capacity(text.len(),);
+    let mut count = 0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let wlen = rest.find(|c: char| !c.is_whitespace(),).unwrap_or(rest.len(),);
+        result.push_str(&rest[..wlen],);
+        rest = &rest[wlen..];
+        let tlen = rest.find(char::is_whitespace,).unwrap_or(rest.len(),);
+        let token = &rest[..tlen];
+        if token.is_empty() {
+            break;
+        }
+        match convert(token, todwork,).map(|x| x.into_iter().next(),) {
+            Ok(Some(x,),) => {
+                result.push_str(&x,);
+                count += 1;
+            },
+            _ => result.push_str(token,),
+        }
+        rest = &rest[tlen..];
+    }
+    (result, count,)
+}
+
+/// Converts the values on a clipboard in place, giving the
+/// number converted
+pub fn replace_clipboard<P: ClipboardProvider,>(ctx: &mut P, todwork: &mut TodInfo,) -> Result<usize, InputError,> {
+    let text = get_clipboard(ctx,)?;
+    let (text, count,) = replace_tokens(&text, todwork,);
+    put_clipboard(ctx, text,)?;
+    Ok(count,)
+}
+
+/// Watches a clipboard for new values to convert
+///
+/// Whatever is on the clipboard when watching starts is
+/// ignored. Content that can't be read as text is ignored
+/// too.
+pub struct ClipWatcher<P: ClipboardProvider,> {
+    ctx:  P,
+    last: Option<String,>,
+}
+
+impl<P: ClipboardProvider,> ClipWatcher<P,> {
+    /// Starts watching a clipboard
+    pub fn new(mut ctx: P) -> ClipWatcher<P,> {
+        let last = ctx.get_contents().ok();
+        ClipWatcher { ctx, last }
+    }
+
+    /// Looks at the clipboard once and, if its contents have
+    /// changed, converts the values in it
+    ///
+    /// Gives the lines for the values that could be
+    /// converted, which may be none
+    pub fn poll(&mut self, todwork: &mut TodInfo,) -> Vec<String,> {
+        let text = match self.ctx.get_contents() {
+            Ok(x,) => x,
+            Err(_,) => return Vec::new(),
+        };
+        if self.last.as_ref() == Some(&text,) {
+            return Vec::new();
+        }
+        let mut result: Vec<String,> = Vec::new();
+        for token in text.split_whitespace() {
+            if let Ok(mut x,) = convert(token, todwork,) {
+                result.append(&mut x,);
+            }
+        }
+        self.last = Some(text,);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOD: &str = "d1e0d68173cc0000";
+    const LINE: &str = "000 d1e0d681 73cc0--- : 2017-01-01 00:00:00.000000";
+
+    #[test]
+    fn copy_lines_puts_one_result_to_a_line() {
+        let mut ctx = MemClipboard::default();
+        copy_lines(&mut ctx, &["a".to_string(), "b".to_string()],).unwrap();
+        assert_eq!(ctx.0, "a\nb\n");
+    }
+
+    #[test]
+    fn replace_clipboard_converts_values_in_place() {
+        let mut ctx = MemClipboard(format!("at {}\tand  zzz\n", TOD),);
+        let mut todwork = TodInfo::new();
+        assert_eq!(replace_clipboard(&mut ctx, &mut todwork,).unwrap(), 1);
+        assert!(ctx.0.starts_with(&format!("at {}", LINE),));
+        assert!(ctx.0.ends_with("\tand  zzz\n",));
+    }
+
+    #[test]
+    fn watcher_converts_only_new_contents() {
+        let mut todwork = TodInfo::new();
+        let mut watch = ClipWatcher::new(MemClipboard(TOD.to_string(),),);
+        assert!(watch.poll(&mut todwork,).is_empty());
+        watch.ctx.0 = format!("x {} y", TOD);
+        let lines = watch.poll(&mut todwork,);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(LINE,));
+        assert!(watch.poll(&mut todwork,).is_empty());
+        watch.ctx.0 = "zzz".to_string();
+        assert!(watch.poll(&mut todwork,).is_empty());
+        watch.ctx.0 = TOD.to_string();
+        assert_eq!(watch.poll(&mut todwork,).len(), 2);
+    }
+}
@@ -0,0 +1,256 @@
+extern crate chrono;
+use self::chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use super::astro::*;
+use super::cics::*;
+use super::db2::*;
+use super::excel::*;
+use super::nettime::*;
+use super::ticks::*;
+use super::todinfo::*;
+use super::tpf::*;
+
+use std::fmt;
+
+/// TAI - UTC before the first leap second, in 1972
+pub const TAI_UTC: i64 = 10;
+
+/// Nanoseconds in a day
+pub const DAY: i128 = 86_400_000_000_000;
+
+/// A clock or timestamp format, as a count of units from an
+/// epoch
+///
+/// Each format in the registry gets a `from-NAME`
+/// subcommand, a `--column NAME` column with its heading, a
+/// `{NAME}` template field, a `:NAME` interactive command,
+/// and a filter pattern. The TOD Clock, date/time, PMC, Unix
+/// and packed decimal formats are built in, with their own
+/// flags and the usual columns: every conversion goes
+/// through the TOD and the date and time, the PMC and Unix
+/// columns are on every line, and Unix values and packed
+/// dates are read with settings (unit, layout) that a
+/// format's `parse` doesn't get.
+pub trait ClockFormat: Sync {
+    /// The short name of the format
+    fn name(&self) -> &'static str;
+
+    /// What the values are, for help
+    fn about(&self) -> &'static str;
+
+    /// Column heading, for text and CSV
+    fn heading(&self) -> &'static str;
+
+    /// Column width, for text
+    fn width(&self) -> usize;
+
+    /// When the count starts
+    fn epoch(&self) -> NaiveDateTime;
+
+    /// Length of a unit, in nanoseconds
+    fn resolution(&self) -> i128;
+
+    /// The lowest and highest counts the format can hold
+    fn range(&self) -> (i128, i128,);
+
+    /// Reads a value as a count
+    fn parse(&self, text: &str,) -> Option<i128,>;
+
+    /// Writes a count as a value
+    fn format(&self, count: i128,) -> String;
+
+    /// Finds the date and time for a count: by default, the
+    /// epoch plus so many units, without leap seconds
+    fn to_instant(&self, count: i128, _todwork: &TodInfo,) -> Option<NaiveDateTime,> { add_nanos(self.epoch(), count.checked_mul(self.resolution(),)?,) }
+
+    /// Finds the count for a date and time: by default, the
+    /// whole units since the epoch
+    fn to_count(&self, date: NaiveDateTime, _todwork: &TodInfo,) -> Option<i128,> {
+        Some(nanos_since(date, self.epoch(),)?.div_euclid(self.resolution(),),)
+    }
+
+    /// The TOD Clock value for a count, for formats that are
+    /// the TOD Clock in another form: these are converted as
+    /// TOD values, and their columns follow the TOD
+    fn tod(&self, _count: i128,) -> Option<Tod,> { None }
+
+    /// Whether the format keeps its own time scale, so that
+    /// `to_instant` gives, and `to_count` is given, the UTC
+    /// date and time, whatever the clock discipline and time
+    /// zone shown: these are converted through the TOD Clock
+    fn absolute(&self) -> bool { false }
+
+    /// Regular expression for values in text, in filter mode
+    ///
+    /// It gives the length of a value too (there's no default
+    /// `--min-len`), so a short one also finds other numbers
+    /// and words in free text: four hex digits for a
+    /// Perpetual Day, say, or five digits for an MJD or an
+    /// Excel serial date. A `--pattern` can add some context.
+    fn pattern(&self) -> &'static str;
+
+    /// Reads a raw binary field as a count, if the format has
+    /// a binary form of that width
+    fn count_from_bytes(&self, _raw: &[u8],) -> Option<i128,> { None }
+}
+
+impl fmt::Debug for dyn ClockFormat {
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result { write!(f, "{}", self.name()) }
+}
+
+/// Adds nanoseconds to a date and time, or *None* if
+/// out-of-range
+pub fn add_nanos(date: NaiveDateTime, n: i128,) -> Option<NaiveDateTime,> {
+    let seconds = n.div_euclid(1_000_000_000,);
+    if seconds.abs() > i128::from(i64::MAX / 1_000,) {
+        return None;
+    }
+    date.checked_add_signed(Duration::seconds(seconds as i64,),)?
+        .checked_add_signed(Duration::nanoseconds(n.rem_euclid(1_000_000_000,) as i64,),)
+}
+
+/// Finds the nanoseconds from one date and time to another
+pub fn nanos_since(date: NaiveDateTime, base: NaiveDateTime,) -> Option<i128,> {
+    let diff = date.signed_duration_since(base,);
+    let seconds = diff.num_seconds();
+    let nanos = (diff - Duration::seconds(seconds,)).num_nanoseconds()?;
+    Some(i128::from(seconds,) * 1_000_000_000 + i128::from(nanos,),)
+}
+
+/// Finds the value of up to so many hex digits, padded with
+/// zeros on the left
+pub fn hex_value(hex: &str, digits: usize,) -> Option<u64,> {
+    if hex.is_empty() || hex.len() > digits || !hex.bytes().all(|b| b.is_ascii_hexdigit(),) {
+        return None;
+    }
+    u64::from_str_radix(hex, 16,).ok()
+}
+
+/// Writes raw bytes as (upper-case) hex digits
+pub fn hex_string(raw: &[u8]) -> String { raw.iter().map(|b| format!("{:02X}", b),).collect() }
+
+/// Finds the UTC date and time of the TOD Clock value in
+/// the work area, whatever the clock discipline and time
+/// zone shown
+pub fn utc_instant(todwork: &TodInfo) -> Option<NaiveDateTime,> {
+    let todbase = NaiveDate::from_ymd(1900, 1, 1,).and_hms(0, 0, 0,);
+    let leaps = i128::from(todwork.lstab.tod_count(todwork.tod,),);
+    add_nanos(todbase, i128::from(todwork.tod.0,) * 1_000 - leaps * 1_000_000_000,)
+}
+
+/// Finds the TOD Clock value for a UTC date and time, with
+/// the leap seconds in effect, or *None* if out-of-range
+pub fn tod_from_utc(utc: NaiveDateTime, todwork: &TodInfo,) -> Option<Tod,> {
+    let todbase = NaiveDate::from_ymd(1900, 1, 1,).and_hms(0, 0, 0,);
+    let leaps = i128::from(todwork.lstab.ls_count(utc.date(),),);
+    let micros = (nanos_since(utc, todbase,)? + leaps * 1_000_000_000).div_euclid(1_000,);
+    if micros >= 0 && micros <= i128::from(u64::MAX,) { Some(Tod(micros as u64,),) } else { None }
+}
+
+/// The registered formats, in the order they are listed
+pub static FORMATS: [&dyn ClockFormat; 17] = [
+    &Abstime,
+    &Db2Timestamp,
+    &Lrsn,
+    &TpfClock::Pday,
+    &TpfClock::ParsDate,
+    &TpfClock::Psc,
+    &Counter::Filetime,
+    &Counter::Ticks,
+    &Counter::JavaMillis,
+    &Counter::Vms,
+    &Excel,
+    &Ntp,
+    &Ptp,
+    &DayCount::Jd,
+    &DayCount::Mjd,
+    &DynamicalTime::Tt,
+    &DynamicalTime::Tdb,
+];
+
+/// Finds a registered format by name
+pub fn find_format(name: &str) -> Option<&'static dyn ClockFormat,> { FORMATS.iter().find(|x| x.name() == name,).cloned() }
+
+/// The names of the registered formats
+pub fn format_names() -> Vec<&'static str,> { FORMATS.iter().map(|x| x.name(),).collect() }
+
+/// Finds the count for the date and time in the work area,
+/// as a value, or *None* if out-of-range
+pub fn format_value(format: &dyn ClockFormat, todwork: &TodInfo,) -> Option<String,> {
+    let (low, high,) = format.range();
+    let date = if format.absolute() { utc_instant(todwork,)? } else { todwork.date };
+    match format.to_count(date, todwork,) {
+        Some(x,) if x >= low && x <= high => Some(format.format(x,),),
+        _ => None,
+    }
+}
+
+/// Converts a value in a registered format, as a date and
+/// time
+pub fn from_clock(a: &str, format: &dyn ClockFormat, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    match format.parse(a,) {
+        None => Err(format!("{} value is invalid: {:?}", format.heading(), a),),
+        Some(x,) => from_clock_count(x, format, todwork,),
+    }
+}
+
+/// Converts a count in a registered format, as a date and
+/// time
+pub fn from_clock_count(count: i128, format: &dyn ClockFormat, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    let (low, high,) = format.range();
+    if count >= low && count <= high {
+        if let Some(x,) = format.tod(count,) {
+            todwork.tod = x;
+            return from_tod_value(todwork,);
+        }
+    }
+    let date = if count < low || count > high { None } else { format.to_instant(count, todwork,) };
+    let invalid = || format!("Can't handle this {} value: {}", format.heading(), format.format(count,));
+    todwork.date = match date {
+        None => return Err(invalid(),),
+        Some(x,) => x,
+    };
+    if format.absolute() {
+        todwork.tod = match tod_from_utc(todwork.date, todwork,) {
+            None => return Err(invalid(),),
+            Some(x,) => x,
+        };
+        return from_tod_value(todwork,);
+    }
+    from_datetime_value(todwork,)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2017-01-01 00:00:00 UTC
+    const TOD: u64 = 0xd1e0d68173cc0;
+
+    /// Converts a value with each clock discipline, and a
+    /// second zone, checking the TOD and the value again
+    fn round_trip(format: &dyn ClockFormat, text: &str,) {
+        for scale in ["UTC", "TAI", "LOR"].iter() {
+            let mut todwork = TodInfo::new();
+            todwork.set_scale(scale,);
+            todwork.set_zones(true, Toffset(Some(7200,),), Toffset(None,),);
+            assert_eq!(from_clock(text, format, &mut todwork,).map(|x| x.len(),), Ok(2), "{} {}", scale, text);
+            assert_eq!(todwork.tod.0, TOD, "{} {}", scale, text);
+            assert_eq!(format_value(format, &todwork,).as_deref(), Some(text), "{}", scale);
+        }
+    }
+
+    #[test]
+    fn network_time_round_trips() {
+        round_trip(&Ptp, "1483228837.000000000",);
+        round_trip(&Ntp, "dc12c500.00000000",);
+    }
+
+    #[test]
+    fn astronomical_time_round_trips() {
+        round_trip(&DynamicalTime::Tt, "2017-01-01T00:01:09.184000",);
+        round_trip(&DynamicalTime::Tdb, "2017-01-01T00:01:09.183930",);
+        round_trip(&DayCount::Jd, "2457754.50000000",);
+        round_trip(&DayCount::Mjd, "57754.00000000",);
+    }
+}
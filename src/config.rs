@@ -0,0 +1,307 @@
+extern crate clap;
+use self::clap::ArgMatches;
+
+use super::args::value;
+
+extern crate toml;
+use self::toml::{Table, Value};
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Settings from the configuration file
+///
+/// The file is `config.toml` in `$XDG_CONFIG_HOME/untod`
+/// (usually `~/.config/untod`), or the one named by
+/// `--config`. Every setting is optional:
+///
+/// ```toml
+/// lzone        = "+1"        # or "off"
+/// lzone-label  = "LON"
+/// azone        = "-5"
+/// azone-label  = "NYC"
+/// zulu         = true
+/// scale        = "utc"       # utc, tai or lor
+/// padding      = "left"      # left, right or intelligent
+/// format       = "csv"       # text or csv
+/// template     = "{date} {time} {zone} {tod}"
+/// leap-seconds = "/usr/share/zoneinfo/leap-seconds.list"
+///
+/// [profiles.prod-lon]
+/// scale        = "tai"
+/// lzone        = "+1"
+/// ```
+///
+/// A profile, chosen by `--profile`, overrides the settings
+/// at the top of the file. Each setting is taken from the
+/// first of:
+/// 1.  the command line
+/// 1.  the environment (`UNTOD_LZONE`, `UNTOD_AZONE`)
+/// 1.  the profile
+/// 1.  the top of the configuration file
+/// 1.  the built-in default
+///
+/// A zone's label is only used with the offset from the
+/// configuration, not one given on the command line or in
+/// the environment.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub lzone:    Option<String,>,
+    pub llabel:   Option<String,>,
+    pub azone:    Option<String,>,
+    pub alabel:   Option<String,>,
+    pub zulu:     Option<bool,>,
+    pub scale:    Option<String,>,
+    pub padding:  Option<String,>,
+    pub format:   Option<String,>,
+    pub template: Option<String,>,
+    pub leapsecs: Option<PathBuf,>,
+}
+
+/// Reasons the configuration can't be used
+#[derive(Debug)]
+pub enum ConfigError {
+    Open(String, io::Error,),
+    Invalid(String, String,),
+}
+impl ConfigError {
+    /// Exit status for the error, following *sysexits.h*
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            ConfigError::Open(..) => 66,
+            ConfigError::Invalid(..) => 78,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
+        match *self {
+            ConfigError::Open(ref name, ref e,) => write!(f, "Can't open {}: {}", name, e),
+            ConfigError::Invalid(ref name, ref e,) => write!(f, "Invalid configuration in {}: {}", name, e),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Config {
+    /// Reads the configuration file, and the profile, named
+    /// on the command line
+    ///
+    /// The default file needn't exist; one named with
+    /// `--config` must.
+    pub fn new_from_args(cmdl: &ArgMatches) -> Result<Config, ConfigError,> {
+        let (path, named,) = match value(cmdl, "config",) {
+            Some(x,) => (Some(PathBuf::from(x,),), true,),
+            None => (default_path(), false,),
+        };
+        let profile = value(cmdl, "profile",);
+        let path = match path {
+            Some(x,) => x,
+            None => return Config::new_from_table(&Table::new(), "(none)", profile,),
+        };
+        let name = path.display().to_string();
+        let text = match fs::read_to_string(&path,) {
+            Ok(x,) => x,
+            Err(ref e,) if !named && e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e,) => return Err(ConfigError::Open(name, e,),),
+        };
+        let table = match text.parse::<Table>() {
+            Ok(x,) => x,
+            Err(e,) => return Err(ConfigError::Invalid(name, e.to_string().trim().to_string(),),),
+        };
+        Config::new_from_table(&table, &name, profile,)
+    }
+
+    /// Builds the configuration from a parsed file, with the
+    /// profile (if any) over the top-level settings
+    pub fn new_from_table(table: &Table, name: &str, profile: Option<&str,>,) -> Result<Config, ConfigError,> {
+        let invalid = |e: String| ConfigError::Invalid(name.to_string(), e,);
+        let mut config = Config::default();
+        config.merge(table,).map_err(invalid,)?;
+        if let Some(p,) = profile {
+            match table.get("profiles",).and_then(|x| x.get(p,),) {
+                Some(Value::Table(x,),) => config.merge(x,).map_err(invalid,)?,
+                _ => return Err(invalid(format!("no profile {:?}", p),),),
+            }
+        }
+        Ok(config,)
+    }
+
+    /// Takes the settings found in a table, replacing any
+    /// already there
+    fn merge(&mut self, table: &Table,) -> Result<(), String,> {
+        for (key, value,) in table {
+            match key.as_str() {
+                "lzone" => self.lzone = Some(offset_value(key, value,)?,),
+                "lzone-label" => self.llabel = Some(string_value(key, value,)?,),
+                "azone" => self.azone = Some(offset_value(key, value,)?,),
+                "azone-label" => self.alabel = Some(string_value(key, value,)?,),
+                "zulu" => match *value {
+                    Value::Boolean(x,) => self.zulu = Some(x,),
+                    _ => return Err(format!("{} must be true or false", key),),
+                },
+                "scale" => self.scale = Some(choice_value(key, value, &["utc", "tai", "lor"],)?,),
+                "padding" => self.padding = Some(choice_value(key, value, &["left", "right", "intelligent"],)?,),
+                "format" => self.format = Some(choice_value(key, value, &["text", "csv"],)?,),
+                "template" => self.template = Some(string_value(key, value,)?,),
+                "leap-seconds" => self.leapsecs = Some(PathBuf::from(string_value(key, value,)?,),),
+                "profiles" => (),
+                _ => return Err(format!("unknown setting {:?}", key),),
+            }
+        }
+        Ok((),)
+    }
+}
+
+/// Where the configuration file is, if there's a home to
+/// put it in
+fn default_path() -> Option<PathBuf,> {
+    let base = match env::var_os("XDG_CONFIG_HOME",) {
+        Some(x,) => PathBuf::from(x,),
+        None => PathBuf::from(env::var_os("HOME",).or_else(|| env::var_os("USERPROFILE",),)?,).join(".config",),
+    };
+    Some(base.join("untod",).join("config.toml",),)
+}
+
+/// A setting that must be a string
+fn string_value(key: &str, value: &Value,) -> Result<String, String,> {
+    match *value {
+        Value::String(ref x,) => Ok(x.clone(),),
+        _ => Err(format!("{} must be a string", key),),
+    }
+}
+
+/// A setting that must be one of a few (lower-case) words
+fn choice_value(key: &str, value: &Value, choices: &[&str],) -> Result<String, String,> {
+    let x = string_value(key, value,)?.to_lowercase();
+    if choices.contains(&x.as_str(),) {
+        Ok(x,)
+    } else {
+        Err(format!("{} must be one of {}", key, choices.join(", ",)),)
+    }
+}
+
+/// A timezone offset, in hours, given as a number or a
+/// string
+fn offset_value(key: &str, value: &Value,) -> Result<String, String,> {
+    match *value {
+        Value::String(ref x,) => Ok(x.clone(),),
+        Value::Integer(x,) => Ok(x.to_string(),),
+        Value::Float(x,) => Ok(x.to_string(),),
+        _ => Err(format!("{} must be an offset in hours", key),),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::args::command;
+    use super::super::todinfo::*;
+    use super::*;
+
+    use std::ffi::OsString;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Held by the tests that depend on `UNTOD_LZONE`
+    static ENV: Mutex<(),> = Mutex::new((),);
+
+    /// Clears `UNTOD_LZONE` for a test, putting it back after
+    struct Lzone {
+        _lock: MutexGuard<'static, (),>,
+        saved: Option<OsString,>,
+    }
+
+    impl Lzone {
+        fn clear() -> Lzone {
+            let lock = ENV.lock().unwrap_or_else(|e| e.into_inner(),);
+            let saved = env::var_os("UNTOD_LZONE",);
+            env::remove_var("UNTOD_LZONE",);
+            Lzone { _lock: lock, saved, }
+        }
+    }
+
+    impl Drop for Lzone {
+        fn drop(&mut self,) {
+            match self.saved {
+                Some(ref x,) => env::set_var("UNTOD_LZONE", x,),
+                None => env::remove_var("UNTOD_LZONE",),
+            }
+        }
+    }
+
+    const FILE: &str = r#"
+        lzone       = "+1"
+        lzone-label = "LON"
+        [profiles.p]
+        lzone       = "+2"
+        lzone-label = "PAR"
+    "#;
+
+    /// The work area for a command line, over the file above
+    fn todwork(args: &[&str], profile: Option<&str,>,) -> TodInfo {
+        let cmdl = command().try_get_matches_from(args,).unwrap();
+        let config = Config::new_from_table(&FILE.parse::<Table>().unwrap(), "test", profile,).unwrap();
+        TodInfo::new_from_args(&cmdl, &config,).unwrap()
+    }
+
+    #[test]
+    fn profile_overrides_file() {
+        let table = FILE.parse::<Table>().unwrap();
+        let config = Config::new_from_table(&table, "test", None,).unwrap();
+        assert_eq!(config.lzone.as_deref(), Some("+1"));
+        let config = Config::new_from_table(&table, "test", Some("p",),).unwrap();
+        assert_eq!(config.lzone.as_deref(), Some("+2"));
+        assert_eq!(config.llabel.as_deref(), Some("PAR"));
+        assert!(Config::new_from_table(&table, "test", Some("q",),).is_err());
+    }
+
+    #[test]
+    fn command_line_then_environment_then_profile_then_file() {
+        let args = ["untod", "from-tod", "0"];
+        let zoned = ["untod", "--lzone", "4", "from-tod", "0"];
+        let _env = Lzone::clear();
+        let x = todwork(&args, None,);
+        assert_eq!((x.loff, x.zone(1, x.loff,)), (Toffset(Some(3600,),), "LON".to_string()));
+        let x = todwork(&args, Some("p",),);
+        assert_eq!((x.loff, x.zone(1, x.loff,)), (Toffset(Some(7200,),), "PAR".to_string()));
+        env::set_var("UNTOD_LZONE", "3",);
+        let x = todwork(&args, Some("p",),);
+        assert_eq!((x.loff, x.zone(1, x.loff,)), (Toffset(Some(10800,),), "UTC+03:00".to_string()));
+        let x = todwork(&zoned, Some("p",),);
+        assert_eq!((x.loff, x.zone(1, x.loff,)), (Toffset(Some(14400,),), "UTC+04:00".to_string()));
+    }
+
+    #[test]
+    fn labels_belong_to_their_zone() {
+        let _env = Lzone::clear();
+        let config = Config::new_from_table(&"lzone = 0\nlzone-label = \"LON\"".parse::<Table>().unwrap(), "test", None,).unwrap();
+        let mut x = TodInfo::new_from_args(&command().try_get_matches_from(["untod", "--tai", "from-tod", "0"],).unwrap(), &config,).unwrap();
+        assert_eq!(x.loff, Toffset(None,));
+        assert_eq!(x.zone(0, x.goff,), "TAI+00:00");
+        x.set_zones(true, Toffset(Some(3600,),), Toffset(None,),);
+        assert_eq!(x.zone(1, x.loff,), "LON TAI");
+    }
+
+    #[test]
+    fn command_line_undoes_the_file() {
+        const FILE: &str = "scale = \"tai\"\nzulu = false\nformat = \"csv\"\ntemplate = \"{tod}\"";
+        let config = Config::new_from_table(&FILE.parse::<Table>().unwrap(), "test", None,).unwrap();
+        let todwork = |args: &[&str]| {
+            TodInfo::new_from_args(&command().try_get_matches_from(args,).unwrap(), &config,).unwrap()
+        };
+        let _env = Lzone::clear();
+        let x = todwork(&["untod", "--lzone", "1", "from-tod", "0"],);
+        assert_eq!((x.cname.as_str(), x.goff, x.csv, x.template.as_deref()), ("TAI", Toffset(None,), true, Some("{tod}",)));
+        let x = todwork(&["untod", "--lzone", "1", "--utc", "--keep-zulu", "--text", "--no-template", "from-tod", "0"],);
+        assert_eq!((x.cname.as_str(), x.goff, x.csv, x.template.as_deref()), ("UTC", Toffset(Some(0,),), false, None));
+        assert!(x.utc);
+        for args in [["untod", "--utc", "--tai"], ["untod", "--csv", "--text"], ["untod", "-z", "--keep-zulu"], ["untod", "--template=x", "--no-template"]] {
+            assert!(command().try_get_matches_from(args,).is_err());
+        }
+    }
+}
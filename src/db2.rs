@@ -0,0 +1,90 @@
+extern crate chrono;
+use self::chrono::{NaiveDate, NaiveDateTime};
+
+use super::clockfmt::*;
+use super::todinfo::*;
+
+/// Db2 TIMESTAMP strings: *yyyy-mm-dd-hh.mm.ss.nnnnnn*, or
+/// the start of one
+pub struct Db2Timestamp;
+
+impl ClockFormat for Db2Timestamp {
+    fn name(&self) -> &'static str { "db2" }
+
+    fn about(&self) -> &'static str { "Db2 TIMESTAMP strings (yyyy-mm-dd-hh.mm.ss.nnnnnn)" }
+
+    fn heading(&self) -> &'static str { "Db2 Timestamp" }
+
+    fn width(&self) -> usize { 26 }
+
+    fn epoch(&self) -> NaiveDateTime { NaiveDate::from_ymd(1, 1, 1,).and_hms(0, 0, 0,) }
+
+    fn resolution(&self) -> i128 { 1_000 }
+
+    /// From 0001-01-01 to the end of 9999
+    fn range(&self) -> (i128, i128,) { (0, 3_652_059 * 86_400_000_000 - 1,) }
+
+    fn parse(&self, text: &str,) -> Option<i128,> {
+        if text.len() < 10 || (text.len() > 10 && text.as_bytes()[10] != b'-') {
+            return None;
+        }
+        let date = finddate(text.to_string(),).ok()?;
+        Some(nanos_since(date, self.epoch(),)? / 1_000,)
+    }
+
+    fn format(&self, count: i128,) -> String {
+        match add_nanos(self.epoch(), count * 1_000,) {
+            Some(x,) => x.format("%F-%H.%M.%S%.6f",).to_string(),
+            None => "-".repeat(26,),
+        }
+    }
+
+    fn pattern(&self) -> &'static str { r"\b[0-9]{4}-[0-9]{2}-[0-9]{2}-[0-9]{2}\.[0-9]{2}\.[0-9]{2}(?:\.[0-9]{1,6})?" }
+}
+
+/// Db2 LRSNs: bits 0-47 of a STCK value (6 bytes), or the
+/// first 10 bytes of a STCKE value (extended), in hex
+///
+/// The LRSN of a data sharing group may be ahead of the
+/// clock by the group's LRSN delta, which isn't allowed for.
+pub struct Lrsn;
+
+impl ClockFormat for Lrsn {
+    fn name(&self) -> &'static str { "lrsn" }
+
+    fn about(&self) -> &'static str { "Db2 LRSNs (6 or 10 bytes, in hex)" }
+
+    fn heading(&self) -> &'static str { "LRSN" }
+
+    fn width(&self) -> usize { 20 }
+
+    fn epoch(&self) -> NaiveDateTime { NaiveDate::from_ymd(1900, 1, 1,).and_hms(0, 0, 0,) }
+
+    fn resolution(&self) -> i128 { 1_000 }
+
+    fn range(&self) -> (i128, i128,) { (0, (1 << 60) - 1,) }
+
+    fn parse(&self, hex: &str,) -> Option<i128,> {
+        let tod = match hex.len() {
+            12 => Tod::new_from_hex(&format!("000{}", hex), &Padding::Right,),
+            20 => Tod::new_from_stcke_hex(&format!("{}000000000000", hex),),
+            _ => None,
+        };
+        tod.map(|x| i128::from(x.0,),)
+    }
+
+    /// Writes the extended form
+    fn format(&self, count: i128,) -> String { format!("{:015x}00000", count) }
+
+    /// An LRSN is the TOD Clock, so it is that of the line
+    fn to_count(&self, _date: NaiveDateTime, todwork: &TodInfo,) -> Option<i128,> { Some(i128::from(todwork.tod.0,),) }
+
+    fn tod(&self, count: i128,) -> Option<Tod,> { Some(Tod(count as u64,),) }
+
+    fn pattern(&self) -> &'static str { r"\b(?:[0-9A-Fa-f]{20}|[0-9A-Fa-f]{12})\b" }
+
+    fn count_from_bytes(&self, raw: &[u8],) -> Option<i128,> {
+        let hex = hex_string(raw,);
+        self.parse(&hex,)
+    }
+}
@@ -0,0 +1,75 @@
+extern crate chrono;
+use self::chrono::{NaiveDate, NaiveDateTime};
+
+use super::astro::day_nanos;
+use super::clockfmt::*;
+use super::todinfo::*;
+
+/// Excel serial dates, with or without a fraction, in the
+/// 1900 or 1904 date system, counted here in nanoseconds
+///
+/// In the 1900 system, day 1 is 1900-01-01, but Excel (like
+/// Lotus 1-2-3) takes 1900 as a leap year: day 60 is the
+/// missing 1900-02-29, and is refused, and the later days
+/// are one ahead. In the 1904 system, day 0 is 1904-01-01.
+pub struct Excel;
+
+impl Excel {
+    /// When the count starts, for a date system and (in the
+    /// 1900 system) whether the date is before March 1900
+    fn base(system1904: bool, early: bool,) -> NaiveDateTime {
+        if system1904 {
+            NaiveDate::from_ymd(1904, 1, 1,)
+        } else if early {
+            NaiveDate::from_ymd(1899, 12, 31,)
+        } else {
+            NaiveDate::from_ymd(1899, 12, 30,)
+        }
+        .and_hms(0, 0, 0,)
+    }
+}
+
+impl ClockFormat for Excel {
+    fn name(&self) -> &'static str { "excel" }
+
+    fn about(&self) -> &'static str { "Excel serial dates, with any fraction (1900 date system, or --excel-1904)" }
+
+    fn heading(&self) -> &'static str { "Excel" }
+
+    fn width(&self) -> usize { 14 }
+
+    fn epoch(&self) -> NaiveDateTime { Excel::base(false, false,) }
+
+    fn resolution(&self) -> i128 { 1 }
+
+    fn range(&self) -> (i128, i128,) { (0, i128::from(i64::MAX,) * 1_000,) }
+
+    fn parse(&self, text: &str,) -> Option<i128,> { day_nanos(text,) }
+
+    fn format(&self, count: i128,) -> String { format!("{:.8}", count as f64 / DAY as f64) }
+
+    fn to_instant(&self, count: i128, todwork: &TodInfo,) -> Option<NaiveDateTime,> {
+        if !todwork.excel1904 && (60 * DAY..61 * DAY).contains(&count,) {
+            return None;
+        }
+        add_nanos(Excel::base(todwork.excel1904, count < 60 * DAY,), count,)
+    }
+
+    fn to_count(&self, date: NaiveDateTime, todwork: &TodInfo,) -> Option<i128,> {
+        let early = date < NaiveDate::from_ymd(1900, 3, 1,).and_hms(0, 0, 0,);
+        nanos_since(date, Excel::base(todwork.excel1904, early,),)
+    }
+
+    fn pattern(&self) -> &'static str { r"\b[0-9]{5}(?:\.[0-9]+)?\b" }
+
+    /// Reads an 8-byte little-endian double, as Excel keeps
+    /// them
+    fn count_from_bytes(&self, raw: &[u8],) -> Option<i128,> {
+        let mut x = [0u8; 8];
+        if raw.len() != 8 {
+            return None;
+        }
+        x.copy_from_slice(raw,);
+        self.parse(&format!("{:.9}", f64::from_le_bytes(x,)),)
+    }
+}
@@ -0,0 +1,163 @@
+extern crate clap;
+use self::clap::ArgMatches;
+
+use super::args::value;
+
+extern crate regex;
+use self::regex::Regex;
+
+use super::todinfo::*;
+
+/// A line of text with its values annotated, and how many
+/// were converted, with why the others couldn't be
+#[derive(Clone, Debug)]
+pub struct Annotated {
+    pub text:      String,
+    pub converted: u64,
+    pub failed:    Vec<String,>,
+}
+
+/// Defines where a converted value goes in a filtered line
+/// *   After: Follows the value, in brackets
+/// *   Replace: Replaces the value
+/// *   End: Goes at the end of the line
+#[derive(Clone, Copy, Debug)]
+pub enum FilterStyle {
+    After,
+    Replace,
+    End,
+}
+
+/// Log filter: finds values embedded in lines of text and
+/// annotates them with their conversions
+///
+/// If the pattern has a capture group, the group is
+/// converted; otherwise the whole match is. Matches shorter
+/// than the minimum length are left alone. (A registered
+/// format's own pattern sets the length, so it has none.)
+#[derive(Debug)]
+pub struct LogFilter {
+    pub re:     Regex,
+    pub minlen: usize,
+    pub style:  FilterStyle,
+}
+
+impl LogFilter {
+    /// Builds a filter from command line arguments, with a
+    /// default pattern to suit the calculation type
+    pub fn new_from_args(cmdl: &ArgMatches, todwork: &TodInfo,) -> Result<LogFilter, String,> {
+        let minlen = match value(cmdl, "minlen",) {
+            None => match todwork.runtype {
+                TodCalc::FromTod => 16,
+                TodCalc::FromUnix => 10,
+                TodCalc::FromClock(_,) => 0,
+                _ => 8,
+            },
+            Some(x,) => match x.parse::<usize>() {
+                Ok(n,) => n,
+                _ => return Err(format!("Invalid length: --min-len {}", x),),
+            },
+        };
+        let pattern = match value(cmdl, "pattern",) {
+            Some(x,) => x.to_string(),
+            None => match todwork.runtype {
+                TodCalc::FromTod => format!(r"\b(?:[0-9A-Fa-f]{{32}}|[0-9A-Fa-f]{{{},16}})\b", minlen.min(16,)),
+                TodCalc::FromPMC => format!(r"\b[0-9A-Fa-f]{{{},8}}\b", minlen.min(8,)),
+                TodCalc::FromUnix => format!(r"\b[0-9]{{{},}}(?:\.[0-9]+)?\b", minlen),
+                TodCalc::FromPacked => r"\b(?:[0-9]{7}[FfCc]|[0-9]{8})(?:@[0-9]{8}(?:[0-9]{4}){0,2})?\b".to_string(),
+                TodCalc::FromClock(x,) => x.pattern().to_string(),
+                TodCalc::FromDateTime => {
+                    r"\b[0-9]{4}(?:-[0-9]{2}-[0-9]{2}|\.[0-9]{3})(?:@[0-9]{2}(?::[0-9]{2}(?::[0-9]{2}(?:\.[0-9]+)?)?)?|-[0-9]{2}\.[0-9]{2}\.[0-9]{2}(?:\.[0-9]+)?)?"
+                        .to_string()
+                },
+            },
+        };
+        let re = match Regex::new(&pattern,) {
+            Ok(x,) => x,
+            Err(e,) => return Err(format!("Invalid pattern: {}", e),),
+        };
+        let style = match value(cmdl, "fstyle",) {
+            Some("replace",) => FilterStyle::Replace,
+            Some("end",) => FilterStyle::End,
+            _ => FilterStyle::After,
+        };
+        Ok(LogFilter { re, minlen, style },)
+    }
+
+    /// Copies a line of text, annotating each value found;
+    /// values that can't be converted are left alone
+    pub fn annotate(&self, line: &str, todwork: &mut TodInfo,) -> Annotated {
+        let mut result = String::new();
+        let mut converted = 0;
+        let mut failed: Vec<String,> = Vec::new();
+        let mut tail: Vec<String,> = Vec::new();
+        let mut last = 0;
+        for caps in self.re.captures_iter(line,) {
+            let m = match caps.get(1,).or_else(|| caps.get(0,),) {
+                Some(x,) => x,
+                None => continue,
+            };
+            if m.as_str().len() < self.minlen {
+                continue;
+            }
+            let conv = match convert(m.as_str(), todwork,).map(|x| x.into_iter().next(),) {
+                Ok(Some(x,),) => x,
+                Ok(None,) => continue,
+                Err(e,) => {
+                    failed.push(e,);
+                    continue;
+                },
+            };
+            converted += 1;
+            result.push_str(&line[last..m.start()],);
+            match self.style {
+                FilterStyle::After => {
+                    result.push_str(m.as_str(),);
+                    result.push_str(&format!(" [{}]", conv),);
+                },
+                FilterStyle::Replace => result.push_str(&conv,),
+                FilterStyle::End => {
+                    result.push_str(m.as_str(),);
+                    tail.push(conv,);
+                },
+            }
+            last = m.end();
+        }
+        result.push_str(&line[last..],);
+        if !tail.is_empty() {
+            result.push_str(&format!("  [{}]", tail.join("] [",)),);
+        }
+        Annotated { text: result, converted, failed, }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::args::{command, runtype};
+    use super::*;
+
+    /// A filter and work area for a command line
+    fn filter(args: &[&str]) -> (LogFilter, TodInfo,) {
+        let cmdl = command().try_get_matches_from(args,).unwrap();
+        let mut todwork = TodInfo::new();
+        todwork.runtype = runtype(&cmdl,);
+        todwork.brief = true;
+        (LogFilter::new_from_args(&cmdl, &todwork,).unwrap(), todwork,)
+    }
+
+    #[test]
+    fn annotates_tod_values() {
+        let (filter, mut todwork,) = filter(&["untod", "--filter"],);
+        let x = filter.annotate("at d1e0d68173cc0000 and d1e0", &mut todwork,);
+        assert_eq!(x.text, "at d1e0d68173cc0000 [2017-01-01 00:00:00.000000 UTC+00:00] and d1e0");
+        assert_eq!(x.converted, 1);
+    }
+
+    #[test]
+    fn annotates_short_clock_values() {
+        let (filter, mut todwork,) = filter(&["untod", "--filter", "from-pday"],);
+        let x = filter.annotate("day 48c2 began", &mut todwork,);
+        assert_eq!(x.text, "day 48c2 [2017-01-01 00:00:00.000000 UTC+00:00] began");
+        assert_eq!(x.converted, 1);
+    }
+}
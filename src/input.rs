@@ -0,0 +1,196 @@
+extern crate clap;
+use self::clap::ArgMatches;
+
+use super::args::values;
+use super::clip::*;
+use super::ebcdic::*;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::vec;
+
+/// Defines the ways in which getting input can fail
+/// *   Open: A file couldn't be opened
+/// *   Read: A file (or STDIN) couldn't be read
+/// *   Clipboard: The clipboard couldn't be used
+#[derive(Debug)]
+pub enum InputError {
+    Open(String, io::Error,),
+    Read(String, io::Error,),
+    Clipboard(String,),
+}
+
+impl InputError {
+    /// Exit status for the error, following *sysexits.h*
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            InputError::Open(..) => 66,
+            InputError::Read(..) => 74,
+            InputError::Clipboard(..) => 69,
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
+        match *self {
+            InputError::Open(ref name, ref e,) => write!(f, "Can't open {}: {}", name, e),
+            InputError::Read(ref name, ref e,) => write!(f, "Can't read {}: {}", name, e),
+            InputError::Clipboard(ref e,) => write!(f, "Can't use the clipboard: {}", e),
+        }
+    }
+}
+
+impl Error for InputError {}
+
+/// Lines of text from a list of files ( - for STDIN ),
+/// read in order, a line at a time
+///
+/// A file that can't be opened or read is reported in
+/// place of its lines, and the next one is started. Bytes
+/// that aren't valid UTF-8 are replaced, unless the text
+/// is in EBCDIC.
+pub struct InputLines {
+    files:    vec::IntoIter<String,>,
+    current:  Option<(String, Box<dyn BufRead,>,),>,
+    encoding: Encoding,
+}
+
+impl InputLines {
+    /// Makes a new set of lines from a list of file names,
+    /// in the given encoding
+    pub fn new(files: Vec<String,>, encoding: Encoding,) -> InputLines {
+        InputLines {
+            files: files.into_iter(),
+            current: None,
+            encoding,
+        }
+    }
+}
+
+impl Iterator for InputLines {
+    type Item = Result<String, InputError,>;
+
+    fn next(&mut self) -> Option<Self::Item,> {
+        loop {
+            if let Some((ref name, ref mut rdr,),) = self.current {
+                let mut buffer: Vec<u8,> = Vec::new();
+                let ends = self.encoding.line_ends();
+                match read_line(rdr, ends, &mut buffer,) {
+                    Ok(0,) => {},
+                    Ok(_,) => {
+                        while buffer.last().is_some_and(|b| ends.contains(b,) || *b == b'\r',) {
+                            buffer.pop();
+                        }
+                        return Some(Ok(self.encoding.decode(&buffer,),),);
+                    },
+                    Err(e,) => {
+                        let name = name.clone();
+                        self.current = None;
+                        return Some(Err(InputError::Read(name, e,),),);
+                    },
+                }
+            }
+            self.current = None;
+            let name = self.files.next()?;
+            match open_input(&name,) {
+                Ok(x,) => self.current = Some((name, x,),),
+                Err(e,) => return Some(Err(e,),),
+            }
+        }
+    }
+}
+
+/// Reads up to, and including, the next line end: any of
+/// the given bytes
+fn read_line(rdr: &mut dyn BufRead, ends: &[u8], buffer: &mut Vec<u8,>,) -> io::Result<usize,> {
+    if let [end] = *ends {
+        return rdr.read_until(end, buffer,);
+    }
+    let mut total = 0;
+    loop {
+        let (done, used,) = {
+            let available = match rdr.fill_buf() {
+                Ok(x,) => x,
+                Err(ref e,) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e,) => return Err(e,),
+            };
+            match available.iter().position(|b| ends.contains(b,),) {
+                Some(i,) => {
+                    buffer.extend_from_slice(&available[..=i],);
+                    (true, i + 1,)
+                },
+                None => {
+                    buffer.extend_from_slice(available,);
+                    (available.is_empty(), available.len(),)
+                },
+            }
+        };
+        rdr.consume(used,);
+        total += used;
+        if done {
+            return Ok(total,);
+        }
+    }
+}
+
+/// Opens an input file ( - for STDIN ) for reading
+pub fn open_input(name: &str) -> Result<Box<dyn BufRead,>, InputError,> {
+    if name == "-" {
+        Ok(Box::new(BufReader::new(io::stdin(),),),)
+    } else {
+        match File::open(name,) {
+            Ok(f,) => Ok(Box::new(BufReader::new(f,),),),
+            Err(e,) => Err(InputError::Open(name.to_string(), e,),),
+        }
+    }
+}
+
+/// The input files named on the command line, or STDIN if
+/// there are none
+pub fn input_files(cmdl: &ArgMatches) -> Vec<String,> {
+    match cmdl.get_many::<String,>("infile",) {
+        Some(x,) => x.cloned().collect(),
+        None => vec!["-".to_string()],
+    }
+}
+
+/// Opens the input text as a stream of lines, either from
+/// the clipboard or from files (or STDIN), or *None* if
+/// neither was requested
+///
+/// Lines are read only as they are needed, so a pipe that
+/// never closes can be followed as it is written to
+pub fn input_lines(cmdl: &ArgMatches) -> Option<Box<dyn Iterator<Item = Result<String, InputError,>,>,>,> {
+    if cmdl.get_flag("clipboard",) {
+        let text = open_clipboard().and_then(|mut ctx| get_clipboard(&mut ctx,),);
+        let lines: Vec<Result<String, InputError,>,> = match text {
+            Ok(x,) => x.lines().map(|x| Ok(x.to_string(),),).collect(),
+            Err(e,) => vec![Err(e,)],
+        };
+        return Some(Box::new(lines.into_iter(),),);
+    }
+
+    if cmdl.contains_id("infile",) || cmdl.get_flag("filter",) {
+        return Some(Box::new(InputLines::new(input_files(cmdl,), Encoding::new_from_args(cmdl,),),),);
+    }
+    None
+}
+
+/// Builds a list of values for conversion either from the
+/// command line  or optionally from the clipboard
+///
+/// Values from a file or STDIN are delivered a line at a
+/// time
+pub fn args_or_elsewhere(cmdl: &ArgMatches) -> Box<dyn Iterator<Item = Result<String, InputError,>,>,> {
+    if let Some(lines,) = input_lines(cmdl,) {
+        return Box::new(lines.flat_map(|line| match line {
+            Ok(x,) => x.split_whitespace().map(|x| Ok(x.to_string(),),).collect(),
+            Err(e,) => vec![Err(e,)],
+        },),);
+    }
+
+    Box::new(values(cmdl,).into_iter().map(Ok,),)
+    }
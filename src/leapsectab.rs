@@ -1,8 +1,14 @@
 extern crate chrono;
-use self::chrono::NaiveDate;
+use self::chrono::{Duration, NaiveDate, NaiveDateTime};
 
+use super::clockfmt::TAI_UTC;
 use super::todinfo::*;
 
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::Path;
+
 /// A table structure for finding leap seconds from a date
 /// or TOD clock
 #[derive(Clone, Debug)]
@@ -164,29 +170,130 @@ impl LeapSecTable {
         ],)
     }
 
+    /// Reads the table from a file in the format of the
+    /// IERS `leap-seconds.list`: lines of NTP seconds (since
+    /// 1900) and TAI-UTC, with comments after `#`
+    pub fn new_from_file(path: &Path) -> io::Result<LeapSecTable,> {
+        let text = fs::read_to_string(path,)?;
+        let base = NaiveDate::from_ymd(1900, 1, 1,);
+        let mut table: Vec<LeapSec,> = Vec::new();
+        for (n, line,) in text.lines().enumerate() {
+            let line = line.split('#',).next().unwrap_or("",);
+            let fields: Vec<&str,> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            let entry = match fields[..] {
+                [ntp, tai,] => match (ntp.parse::<u64>(), tai.parse::<i64>(),) {
+                    (Ok(ntp,), Ok(tai,),) if ntp % 86_400 == 0 && tai >= 10 => Some(LeapSec {
+                        day:   base + Duration::days((ntp / 86_400) as i64,),
+                        tod:   (ntp + (tai - 10) as u64) * 1_000_000,
+                        count: tai - 10,
+                    },),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match entry {
+                Some(x,) => table.push(x,),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line {} is not a leap-second entry", n + 1),
+                    ),)
+                },
+            }
+        }
+        table.sort_by_key(|x| Reverse(x.day,),);
+        table.push(LeapSec {
+            day:   NaiveDate::from_ymd(0000, 1, 1,),
+            tod:   0x0000_0000_0000_0000,
+            count: 0,
+        },);
+        Ok(LeapSecTable(table,),)
+    }
+
+    /// Lists the leap-seconds, oldest first, each with the
+    /// date and TOD Clock value at which it took effect, and
+    /// the count from then on
+    pub fn lines(&self, csv: bool,) -> Vec<String,> {
+        self.0
+            .iter()
+            .rev()
+            .filter(|x| x.count > 0,)
+            .map(|x| if csv {
+                format!("{},{},{}", x.day, Tod(x.tod,), x.count)
+            } else {
+                format!("{} {} {:+}", x.day, Tod(x.tod,), x.count)
+            },)
+            .collect()
+    }
+
     /// Search for leap-seconds based on the TodInfo date
     pub fn ls_search_day(&self, todwork: &TodInfo,) -> i64 {
-        let thedate = todwork.date.date();
         if todwork.utc {
-            match self.0.iter().find(|x| x.day <= thedate,) {
-                Some(x,) => x.count,
-                None => self.0[self.0.len() - 1].count,
-            }
+            self.ls_count(todwork.date.date(),)
         } else {
             0
         }
     }
 
+    /// Number of leap-seconds in effect on a date, whatever
+    /// the clock discipline
+    pub fn ls_count(&self, thedate: NaiveDate,) -> i64 {
+        match self.0.iter().find(|x| x.day <= thedate,) {
+            Some(x,) => x.count,
+            None => self.0[self.0.len() - 1].count,
+        }
+    }
+
+    /// Finds the TAI date and time for a UTC one
+    pub fn utc_to_tai(&self, utc: NaiveDateTime,) -> NaiveDateTime {
+        utc + Duration::seconds(TAI_UTC + self.ls_count(utc.date(),),)
+    }
+
+    /// Finds the UTC date and time for a TAI one
+    ///
+    /// The leap seconds are looked up for the TAI date first,
+    /// then again for the UTC date that gives, which may be
+    /// the day before.
+    pub fn tai_to_utc(&self, tai: NaiveDateTime,) -> NaiveDateTime {
+        let utc = tai - Duration::seconds(TAI_UTC + self.ls_count(tai.date(),),);
+        tai - Duration::seconds(TAI_UTC + self.ls_count(utc.date(),),)
+    }
+
     /// Search for leap-seconds based on the TodInfo TOD
     /// value
     pub fn ls_search_tod(&self, todwork: &TodInfo,) -> i64 {
         if todwork.utc {
-            match self.0.iter().find(|x| x.tod <= todwork.tod.0,) {
-                Some(x,) => x.count,
-                None => self.0[0].count,
-            }
+            self.tod_count(todwork.tod,)
         } else {
             0
         }
     }
+
+    /// Number of leap-seconds in effect at a TOD Clock value,
+    /// whatever the clock discipline
+    pub fn tod_count(&self, tod: Tod,) -> i64 {
+        match self.0.iter().find(|x| x.tod <= tod.0,) {
+            Some(x,) => x.count,
+            None => self.0[0].count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utc_and_tai_across_a_leap_second() {
+        let lstab = LeapSecTable::new();
+        let before = NaiveDate::from_ymd(2016, 12, 31,).and_hms(23, 59, 59,);
+        let after = NaiveDate::from_ymd(2017, 1, 1,).and_hms(0, 0, 0,);
+        assert_eq!(lstab.utc_to_tai(before,), NaiveDate::from_ymd(2017, 1, 1,).and_hms(0, 0, 35,));
+        assert_eq!(lstab.utc_to_tai(after,), NaiveDate::from_ymd(2017, 1, 1,).and_hms(0, 0, 37,));
+        assert_eq!(lstab.tai_to_utc(lstab.utc_to_tai(before,),), before);
+        assert_eq!(lstab.tai_to_utc(lstab.utc_to_tai(after,),), after);
+    }
 }
@@ -6,6 +6,13 @@
 //! PARS Perpetual Minute Clock, and Date/Time
 //! 
 //! * Converts values from command line or from clipboard
+//! * Puts results on the clipboard, or converts the values
+//!   on the clipboard in place
+//! * Watches the clipboard, converting values as they are
+//!   copied
+//! * Converts interactively, with settings that can be
+//!   changed as it goes
+//! * Shows the current time, live, in all the clock formats
 //! * Converts for up to three time zones (Zulu/Greenwich, Local, Alternate)
 //! * Supports three clock discplines:
 //!   - UTC, with allowance for leap seconds
@@ -13,13 +20,113 @@
 //!   - TAI, without leap seconds  
 //! 
 //! Input for a given run can be hex TOD clock values, 
-//! hex Perpetual Minute Clock values, or Date and Time values.
+//! hex Perpetual Minute Clock values, Date and Time values,
+//! packed decimal dates (as from the TIME macro) or zoned
+//! decimal ones, or CICS ABSTIME values (`from-abstime`,
+//! which can also be shown as an extra column with
+//! `--column abstime`).
 //! Dates can be specified as *yyyy.ddd* or as *yyyy-mm-dd*.
 //! Partial date and time combinations are padded on the right.
+//! Db2 TIMESTAMP strings are accepted as dates, and can be
+//! shown with `--column db2`; Db2 log LRSNs (6 or 10 bytes)
+//! convert with `from-lrsn`.
+//!
+//! Besides the PMC, the z/TPF Perpetual Day, 4-byte PARS
+//! date and Perpetual Second Clock (all counted from
+//! 1966-01-03) can be converted (`from-pday`,
+//! `from-pdate`, `from-psc`) and shown as columns.
+//! So can the counters of other platforms: Windows FILETIME,
+//! .NET ticks and Java epoch milliseconds. Unix time can
+//! have a fraction, or be in milli-, micro- or nanoseconds
+//! (`--unix-unit`, or found from the number of digits); the
+//! Unix column shows any fraction, or another unit with
+//! `--unix-out` (`--unix-ms` sets both to milliseconds).
+//! NTP timestamps (UTC, with eras) and PTP timestamps (TAI,
+//! with the leap seconds allowed for) are handled too.
+//! For astronomical work, there are Julian Days and MJDs,
+//! in and out, and Terrestrial and Barycentric Dynamical
+//! Time out. (The usual *Julian* column is the ordinal
+//! date, *yyyy.ddd*.)
+//!
+//! OpenVMS system times and Excel serial dates (in the 1900
+//! date system, leap-year bug and all, or `--excel-1904`)
+//! convert both ways as well.
+//!
+//! These formats are kept in a registry (`clockfmt`): each
+//! gives its epoch, resolution, range, and how to read and
+//! write its values, and so gets a `from-NAME` subcommand,
+//! a `--column NAME` column, a `{NAME}` template field, and
+//! a `:NAME` interactive command without further work.
+//!
+//! The kind of value can be chosen with a subcommand
+//! (`from-tod`, `from-date`, `from-pmc`, `from-unix`) or,
+//! as before, with a flag (none, `-d`, `-m`, `-u`); the
+//! options can go before or after the subcommand. `leap`
+//! lists the leap seconds.
+//!
+//! The `diff`, `add` and `sub` subcommands do arithmetic
+//! on TOD clock values; `range` generates the values for
+//! a time window at a given step. (A TOD value that is
+//! spelt `add` needs `from-tod add`.)
+//!
+//! In filter mode, text passes through unchanged, except
+//! that the values found in it are annotated with their
+//! conversions. `--summary` counts them, and `--strict`
+//! stops at the first match that can't be converted. Some
+//! default patterns are too loose for free text: a z/TPF
+//! Perpetual Day is any four hex digits, and an MJD or an
+//! Excel serial date any five-digit number, so give those a
+//! `--pattern` with some context.
+//!
+//! Defaults for the time zones (with labels), clock
+//! discipline, padding and output format can be kept in
+//! `~/.config/untod/config.toml`, with named profiles for
+//! different systems. Command-line options and environment
+//! variables take precedence over them.
+//!
+//! In binary mode, the input is fixed-length records, each
+//! with a raw STCK or STCKE value at a given offset. In SMF
+//! mode, it is an SMF dump, whose header dates and times
+//! (and, if wanted, a TOD field) are converted.
+//!
+//! Text input and output can be in EBCDIC (code page 037
+//! or 1047), for files transferred from the host as they
+//! are. (Not in interactive or live mode, which are for a
+//! terminal.)
+//!
+//! Input can come from several files in turn. If any can't
+//! be read, `untod` carries on with the rest and exits with
+//! status 66 (can't open), 74 (can't read), or 69 (no
+//! clipboard). Values that can't be converted are reported
+//! on STDERR, and give exit status 65; `--strict` stops at
+//! the first of them.
+//!
+//! On X11 the clipboard belongs to the program that set it,
+//! so results put there by `--to-clipboard` or
+//! `--replace-clipboard` are lost when `untod` exits, unless
+//! a clipboard manager keeps them.
 
 #[macro_use]
 extern crate clap;
 
 pub mod args;
+pub mod astro;
+pub mod binary;
+pub mod cics;
+pub mod clip;
+pub mod clockfmt;
+pub mod config;
+pub mod db2;
+pub mod ebcdic;
+pub mod excel;
+pub mod filter;
+pub mod input;
 pub mod leapsectab;
+pub mod live;
+pub mod nettime;
+pub mod packed;
+pub mod repl;
+pub mod ticks;
+pub mod todarith;
 pub mod todinfo;
+pub mod tpf;
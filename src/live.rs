@@ -0,0 +1,65 @@
+extern crate chrono;
+use self::chrono::Utc;
+
+use super::todinfo::*;
+
+use std::io::{self, Write};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// TOD Clock value (microseconds) at the GPS epoch,
+/// 1980-01-06T00:00:00 UTC
+pub const GPS_EPOCH: u64 = 0x0008_F809_FDBB_7440 + 5 * 86_400_000_000;
+
+/// Formats a TOD Clock value as GPS time: *week:seconds*
+pub fn gps_text(tod: Tod) -> String {
+    if tod.0 < GPS_EPOCH {
+        return "----:------".to_string();
+    }
+    let gsec = (tod.0 - GPS_EPOCH) / 1_000_000;
+    format!("{:4}:{:06}", gsec / 604_800, gsec % 604_800)
+}
+
+/// Converts the current time, for each timezone, giving
+/// the usual line with GPS time added
+///
+/// The TOD Clock is set from the system clock with
+/// leap-seconds, as a real one would be, whatever the
+/// clock discipline shown.
+pub fn live_lines(todwork: &mut TodInfo) -> Result<Vec<String,>, String,> {
+    todwork.date = Utc::now().naive_utc();
+    let lsec = todwork.lstab.ls_count(todwork.date.date(),);
+    let (zsec, zmic,) = get_sec_mic(todwork,);
+    todwork.tod = Tod((zsec + lsec as u64) * 1_000_000 + zmic,);
+    let gps = gps_text(todwork.tod,);
+    let sep = if todwork.csv { "," } else { " " };
+    Ok(from_tod_value(todwork,)?.into_iter().map(|x| format!("{}{}{}", x, sep, gps),).collect(),)
+}
+
+/// Runs the live clock display until interrupted,
+/// refreshing at every interval
+///
+/// On a terminal, the lines are redrawn in place; otherwise
+/// they are written afresh at every tick.
+pub fn live<W: Write,>(mut output: W, interval: Duration, tty: bool, todwork: &mut TodInfo,) -> io::Result<(),> {
+    let mut drawn = 0;
+    loop {
+        let lines = match live_lines(todwork,) {
+            Ok(x,) => x,
+            Err(e,) => vec![e],
+        };
+        if tty && drawn > 0 {
+            write!(output, "\x1b[{}A", drawn)?;
+        }
+        for line in &lines {
+            if tty {
+                writeln!(output, "\r{}\x1b[K", line)?;
+            } else {
+                writeln!(output, "{}", line)?;
+            }
+        }
+        drawn = lines.len();
+        output.flush()?;
+        sleep(interval,);
+    }
+}
@@ -1,28 +1,310 @@
 extern crate untod;
-use untod::args::utargs;
+use untod::args::{utargs, value};
+use untod::binary::*;
+use untod::clip::*;
+use untod::config::*;
+use untod::ebcdic::*;
+use untod::filter::*;
+use untod::input::*;
+use untod::live::*;
+use untod::repl::*;
+use untod::todarith::*;
 use untod::todinfo::*;
 
+use std::io::{self, IsTerminal, Write};
+use std::process::exit;
+use std::thread::sleep;
+use std::time::Duration;
+
 fn main() {
     let cmdl = utargs();
-    let mut todwork = TodInfo::new_from_args(&cmdl,);
-    let vlist = args_or_elsewhere(&cmdl,);
-    if cmdl.is_present("headers",) {
-        if cmdl.is_present("csv",) {
-            println!("ExtTOD,Date,Time,Zone,Julian,D,Perp,Unix,Leap");
-        } else {
-            println!("Ext       TOD              Date          Time        Zone     Julian   D    Perp        Unix      Leap");
-            println!("--- ----------------- : ---------- --------------- --------- -------- --- -------- -------------- ----");
+    let mut todwork = match Config::new_from_args(&cmdl,).and_then(|x| TodInfo::new_from_args(&cmdl, &x,),) {
+        Ok(x,) => x,
+        Err(e,) => {
+            eprintln!("untod: {}", e);
+            exit(e.exit_code(),);
+        },
+    };
+    let mut tally = Tally::new(
+        cmdl.get_flag("strict",),
+        cmdl.get_flag("summary",),
+        cmdl.get_flag("toclip",),
+        Encoding::new_from_args(&cmdl,),
+    );
+    if cmdl.get_flag("replace",) {
+        match open_clipboard().and_then(|mut ctx| replace_clipboard(&mut ctx, &mut todwork,),) {
+            Ok(x,) => tally.converted += x as u64,
+            Err(e,) => tally.unreadable(&e,),
         }
+        tally.finish();
     }
-    for a in vlist {
-        let result: Vec<String,> = match todwork.runtype {
-            TodCalc::FromTod => from_tod(&a, &mut todwork,),
-            TodCalc::FromDateTime => from_datetime(&a, &mut todwork,),
-            TodCalc::FromPMC => from_perpetual(&a, &mut todwork,),
-            TodCalc::FromUnix => from_unix(&a, &mut todwork,),
+    if cmdl.get_flag("interactive",) {
+        let stdin = io::stdin();
+        let prompt = stdin.is_terminal();
+        if let Err(e,) = interactive(stdin.lock(), io::stdout(), prompt, &mut todwork,) {
+            eprintln!("untod: {}", e);
+            exit(74,);
+        }
+        exit(0,);
+    }
+    if cmdl.get_flag("filter",) {
+        let filter = match LogFilter::new_from_args(&cmdl, &todwork,) {
+            Ok(x,) => x,
+            Err(e,) => {
+                eprintln!("untod: {}", e);
+                exit(64,);
+            },
+        };
+        for line in input_lines(&cmdl,).into_iter().flatten() {
+            match line {
+                Ok(x,) => tally.annotated(filter.annotate(&x, &mut todwork,),),
+                Err(e,) => tally.unreadable(&e,),
+            }
+        }
+        tally.finish();
+    }
+    if let Some(("leap", _,),) = cmdl.subcommand() {
+        if cmdl.get_flag("headers",) {
+            if cmdl.get_flag("csv",) {
+                tally.print("Date,TOD,Leap".to_string(),);
+            } else {
+                tally.print("   Date       Ext       TOD        Leap".to_string(),);
+                tally.print("---------- --- ----------------- ----".to_string(),);
+            }
+        }
+        for line in todwork.lstab.lines(todwork.csv,) {
+            tally.print(line,);
+        }
+        tally.finish();
+    }
+    if let Some(("diff", sub,),) = cmdl.subcommand() {
+        if cmdl.get_flag("headers",) {
+            for line in diff_headers(&todwork,) {
+                tally.print(line,);
+            }
+        }
+        tally.lines(tod_diff(value(sub, "from",).unwrap(), value(sub, "to",).unwrap(), &mut todwork,),);
+        tally.finish();
+    }
+    if cmdl.get_flag("headers",) {
+        for line in todwork.headers() {
+            tally.print(line,);
+        }
+    }
+    if cmdl.get_flag("binary",) || cmdl.get_flag("smf",) {
+        let smf = cmdl.get_flag("smf",);
+        let field = match BinaryField::new_from_args(&cmdl,) {
+            Ok(x,) => x,
+            Err(e,) => {
+                eprintln!("untod: {}", e);
+                exit(64,);
+            },
+        };
+        let framing = if smf { Framing::Rdw } else { Framing::Fixed(field.reclen,) };
+        let tods = !smf || cmdl.contains_id("foffset",);
+        for record in Records::new(input_files(&cmdl,), framing,) {
+            match record {
+                Ok(x,) => {
+                    if smf {
+                        tally.lines(smf_header(&x, &mut todwork,),);
+                    }
+                    if tods {
+                        tally.lines(field.convert(&x, &mut todwork,),);
+                    }
+                },
+                Err(e,) => tally.unreadable(&e,),
+            }
+        }
+        tally.finish();
+    }
+    if cmdl.get_flag("live",) {
+        let interval = match Interval::new_from_str(value(&cmdl, "interval",).unwrap_or("1s",),) {
+            Some(x,) if x.0 > 0 => Duration::from_micros(x.0 as u64,),
+            _ => {
+                eprintln!("untod: Invalid interval: --interval {}", value(&cmdl, "interval",).unwrap());
+                exit(64,);
+            },
+        };
+        let stdout = io::stdout();
+        let tty = stdout.is_terminal();
+        if let Err(e,) = live(stdout.lock(), interval, tty, &mut todwork,) {
+            eprintln!("untod: {}", e);
+            exit(74,);
+        }
+    }
+    if cmdl.get_flag("watch",) {
+        let interval = match Interval::new_from_str(value(&cmdl, "interval",).unwrap_or("500ms",),) {
+            Some(x,) if x.0 > 0 => Duration::from_micros(x.0 as u64,),
+            _ => {
+                eprintln!("untod: Invalid interval: --interval {}", value(&cmdl, "interval",).unwrap());
+                exit(64,);
+            },
         };
-        for line in result {
-            println!("{}", line);
+        let mut watcher = match open_clipboard() {
+            Ok(x,) => ClipWatcher::new(x,),
+            Err(e,) => {
+                tally.unreadable(&e,);
+                tally.finish();
+            },
+        };
+        loop {
+            for line in watcher.poll(&mut todwork,) {
+                tally.print(line,);
+            }
+            sleep(interval,);
+        }
+    }
+    if let Some((name @ "add", sub,),) | Some((name @ "sub", sub,),) = cmdl.subcommand() {
+        tally.lines(tod_add(
+            value(sub, "tod",).unwrap(),
+            value(sub, "interval",).unwrap(),
+            name == "sub",
+            &mut todwork,
+        ),);
+        tally.finish();
+    }
+    if let Some(("range", sub,),) = cmdl.subcommand() {
+        match date_range(value(sub, "start",).unwrap(), value(sub, "end",).unwrap(), value(sub, "step",).unwrap(), &mut todwork,) {
+            Ok(steps,) => {
+                for x in steps {
+                    tally.lines(x,);
+                }
+            },
+            Err(e,) => tally.lines(Err(e,),),
+        }
+        tally.finish();
+    }
+    let vlist = args_or_elsewhere(&cmdl,);
+    for a in vlist {
+        match a {
+            Ok(x,) => tally.lines(convert(&x, &mut todwork,),),
+            Err(e,) => tally.unreadable(&e,),
+        }
+    }
+    tally.finish();
+}
+
+/// Keeps count of the values converted and failed, and the
+/// exit status for the run: that of the first error
+///
+/// Results go to STDOUT, errors to STDERR. A value that
+/// can't be converted gives status 65; input errors give
+/// their own status. If wanted, results are kept for the
+/// clipboard too. Results are written in the output
+/// encoding; errors are always UTF-8.
+struct Tally {
+    converted:  u64,
+    failed:     u64,
+    unreadable: u64,
+    status:     i32,
+    strict:     bool,
+    summary:    bool,
+    copy:       Option<Vec<String,>,>,
+    encoding:   Encoding,
+}
+
+impl Tally {
+    /// Makes a new, empty, tally
+    fn new(strict: bool, summary: bool, toclip: bool, encoding: Encoding,) -> Tally {
+        Tally {
+            converted: 0,
+            failed: 0,
+            unreadable: 0,
+            status: 0,
+            strict,
+            summary,
+            copy: if toclip { Some(Vec::new(),) } else { None },
+            encoding,
+        }
+    }
+
+    /// Prints a line of output
+    ///
+    /// STDOUT only flushes itself at a line feed, so EBCDIC
+    /// lines, which end with NL, are flushed here.
+    fn print(&mut self, line: String,) {
+        let mut bytes = self.encoding.encode(&line,);
+        bytes.push(self.encoding.newline(),);
+        let mut stdout = io::stdout();
+        let written = stdout.write_all(&bytes,).and_then(|_| match self.encoding {
+            Encoding::Utf8 => Ok((),),
+            _ => stdout.flush(),
+        },);
+        if let Err(e,) = written {
+            eprintln!("untod: Can't write the results: {}", e);
+            exit(74,);
+        }
+        if let Some(ref mut copy,) = self.copy {
+            copy.push(line,);
+        }
+    }
+
+    /// Prints the result of a conversion, or reports why it
+    /// failed
+    fn lines(&mut self, result: Result<Vec<String,>, String,>,) {
+        match result {
+            Ok(x,) => {
+                self.converted += 1;
+                for line in x {
+                    self.print(line,);
+                }
+            },
+            Err(e,) => {
+                self.failed += 1;
+                eprintln!("untod: {}", e);
+                self.fail(65,);
+            },
+        }
+    }
+
+    /// Prints a filtered line, counting its values; those
+    /// that matched but couldn't be converted are only
+    /// reported (and stop) when strict, as the pattern may
+    /// well match other things
+    fn annotated(&mut self, line: Annotated,) {
+        self.converted += line.converted;
+        self.failed += line.failed.len() as u64;
+        self.print(line.text,);
+        if self.strict {
+            if let Some(e,) = line.failed.first() {
+                eprintln!("untod: {}", e);
+                self.fail(65,);
+            }
+        }
+    }
+
+    /// Reports an input error
+    fn unreadable(&mut self, e: &InputError,) {
+        self.unreadable += 1;
+        eprintln!("untod: {}", e);
+        self.fail(e.exit_code(),);
+    }
+
+    /// Records an error, stopping now if strict
+    fn fail(&mut self, status: i32,) {
+        if self.status == 0 {
+            self.status = status;
+        }
+        if self.strict {
+            self.finish();
+        }
+    }
+
+    /// Puts the results on the clipboard and reports the
+    /// counts, if wanted, and exits
+    fn finish(&mut self) -> ! {
+        if let Some(copy,) = self.copy.take() {
+            if let Err(e,) = open_clipboard().and_then(|mut ctx| copy_lines(&mut ctx, &copy,),) {
+                self.strict = false;
+                self.unreadable(&e,);
+            }
+        }
+        if self.summary {
+            eprintln!(
+                "untod: {} converted, {} failed, {} input errors",
+                self.converted, self.failed, self.unreadable
+            );
         }
+        exit(self.status,);
     }
 }
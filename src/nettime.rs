@@ -0,0 +1,132 @@
+extern crate chrono;
+use self::chrono::{NaiveDate, NaiveDateTime};
+
+use super::clockfmt::*;
+use super::todinfo::*;
+
+/// Nanoseconds in a second
+const SECOND: i128 = 1_000_000_000;
+
+/// NTP timestamps: 16 hex digits, or *seconds.fraction* in
+/// hex, as `ntpq` shows, counted here in nanoseconds
+///
+/// The seconds wrap every 136 years, so values with the top
+/// bit off are taken to be in era 1 (from 2036-02-07), and
+/// those with it on in era 0 (from 1968-01-20). NTP time is
+/// UTC, without leap seconds. Values are written without the
+/// era.
+pub struct Ntp;
+
+impl ClockFormat for Ntp {
+    fn name(&self) -> &'static str { "ntp" }
+
+    fn about(&self) -> &'static str { "NTP timestamps (16 hex digits, or seconds.fraction in hex)" }
+
+    fn heading(&self) -> &'static str { "NTP" }
+
+    fn width(&self) -> usize { 17 }
+
+    /// The start of NTP era 0, as for the TOD Clock
+    fn epoch(&self) -> NaiveDateTime { NaiveDate::from_ymd(1900, 1, 1,).and_hms(0, 0, 0,) }
+
+    fn resolution(&self) -> i128 { 1 }
+
+    /// To the end of the era 1 values that can be read
+    fn range(&self) -> (i128, i128,) { (0, (3 << 31) * SECOND - 1,) }
+
+    fn parse(&self, text: &str,) -> Option<i128,> {
+        let (seconds, fraction,) = match text.find('.',) {
+            Some(i,) if i <= 8 && text.len() - i - 1 <= 8 => {
+                (hex_value(&text[..i], 8,)?, hex_value(&format!("{:0<8}", &text[i + 1..]), 8,)?,)
+            },
+            None if text.len() == 16 => (hex_value(&text[..8], 8,)?, hex_value(&text[8..], 8,)?,),
+            _ => return None,
+        };
+        let seconds = if seconds & 0x8000_0000 == 0 { seconds + (1 << 32) } else { seconds };
+        Some(i128::from(seconds,) * SECOND + ((i128::from(fraction,) * SECOND) >> 32),)
+    }
+
+    fn format(&self, count: i128,) -> String {
+        let nanos = count % SECOND;
+        format!("{:08x}.{:08x}", (count / SECOND) & 0xffff_ffff, (nanos << 32) / SECOND)
+    }
+
+    fn absolute(&self) -> bool { true }
+
+    fn pattern(&self) -> &'static str { r"\b[0-9A-Fa-f]{8}\.?[0-9A-Fa-f]{8}\b" }
+
+    /// Reads the 8-byte field
+    fn count_from_bytes(&self, raw: &[u8],) -> Option<i128,> {
+        if raw.len() != 8 {
+            return None;
+        }
+        let hex = hex_string(raw,);
+        self.parse(&hex,)
+    }
+}
+
+/// PTP timestamps: 20 hex digits (48-bit seconds and 32-bit
+/// nanoseconds), or decimal *seconds.nanoseconds*, counted
+/// here in nanoseconds
+///
+/// PTP counts TAI seconds, so the leap seconds in effect
+/// are taken off, and put back on.
+pub struct Ptp;
+
+impl ClockFormat for Ptp {
+    fn name(&self) -> &'static str { "ptp" }
+
+    fn about(&self) -> &'static str { "PTP timestamps, in TAI (20 hex digits, or decimal seconds.nanoseconds)" }
+
+    fn heading(&self) -> &'static str { "PTP" }
+
+    fn width(&self) -> usize { 20 }
+
+    /// The start of PTP time (TAI, not UTC)
+    fn epoch(&self) -> NaiveDateTime { NaiveDate::from_ymd(1970, 1, 1,).and_hms(0, 0, 0,) }
+
+    fn resolution(&self) -> i128 { 1 }
+
+    fn range(&self) -> (i128, i128,) { (0, i128::from(i64::MAX / 1_000,) * SECOND,) }
+
+    fn parse(&self, text: &str,) -> Option<i128,> {
+        let (seconds, nanos,) = if text.len() == 20 && !text.contains('.',) {
+            (hex_value(&text[..12], 12,)?, hex_value(&text[12..], 8,)?,)
+        } else {
+            let mut parts = text.splitn(2, '.',);
+            let seconds = parts.next()?;
+            let nanos = parts.next().unwrap_or("",);
+            if seconds.is_empty() || nanos.len() > 9 || !seconds.bytes().chain(nanos.bytes(),).all(|b| b.is_ascii_digit(),) {
+                return None;
+            }
+            (seconds.parse::<u64>().ok()?, format!("{:0<9}", nanos).parse::<u64>().ok()?,)
+        };
+        if nanos >= 1_000_000_000 {
+            return None;
+        }
+        Some(i128::from(seconds,) * SECOND + i128::from(nanos,),)
+    }
+
+    fn format(&self, count: i128,) -> String { format!("{}.{:09}", count / SECOND, count % SECOND) }
+
+    fn to_instant(&self, count: i128, todwork: &TodInfo,) -> Option<NaiveDateTime,> {
+        Some(todwork.lstab.tai_to_utc(add_nanos(self.epoch(), count,)?,),)
+    }
+
+    fn to_count(&self, date: NaiveDateTime, todwork: &TodInfo,) -> Option<i128,> {
+        nanos_since(todwork.lstab.utc_to_tai(date,), self.epoch(),)
+    }
+
+    fn absolute(&self) -> bool { true }
+
+    fn pattern(&self) -> &'static str { r"\b(?:[0-9A-Fa-f]{20}|[0-9]{10}\.[0-9]{9})\b" }
+
+    /// Reads the 10-byte field
+    fn count_from_bytes(&self, raw: &[u8],) -> Option<i128,> {
+        if raw.len() != 10 {
+            return None;
+        }
+        let hex = hex_string(raw,);
+        self.parse(&hex,)
+    }
+}
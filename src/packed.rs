@@ -0,0 +1,204 @@
+extern crate chrono;
+use self::chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use super::clockfmt::hex_string;
+use super::todinfo::*;
+
+/// Defines the layout of a packed decimal date, as given by
+/// the TIME macro (`DATETYPE=`):
+/// *   Auto: Any of these, as the value suggests: signed
+///     values are *0CYYDDDF* if they start with 00 or 01,
+///     otherwise *YYYYDDDF*; unsigned values are *YYYYMMDD*
+/// *   Cyyddd: *0CYYDDDF*, where *C* is 0 for 19xx and 1
+///     for 20xx (`TIME DEC`)
+/// *   Yyyyddd: *YYYYDDDF*
+/// *   Yyyymmdd: *YYYYMMDD*, unsigned
+/// *   Mmddyyyy: *MMDDYYYY*, unsigned
+/// *   Ddmmyyyy: *DDMMYYYY*, unsigned
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PackedFormat {
+    Auto,
+    Cyyddd,
+    Yyyyddd,
+    Yyyymmdd,
+    Mmddyyyy,
+    Ddmmyyyy,
+}
+
+impl PackedFormat {
+    /// Finds the layout from its name
+    pub fn new_from_str(name: &str) -> Option<PackedFormat,> {
+        match name.to_lowercase().as_str() {
+            "auto" => Some(PackedFormat::Auto,),
+            "cyyddd" | "0cyydddf" => Some(PackedFormat::Cyyddd,),
+            "yyyyddd" | "yyyydddf" => Some(PackedFormat::Yyyyddd,),
+            "yyyymmdd" => Some(PackedFormat::Yyyymmdd,),
+            "mmddyyyy" => Some(PackedFormat::Mmddyyyy,),
+            "ddmmyyyy" => Some(PackedFormat::Ddmmyyyy,),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the value of a positive packed decimal number
+///
+/// The last half-byte must be a sign: *F* or *C*.
+pub fn unpack(packed: &[u8]) -> Option<u64,> {
+    let (last, lead,) = packed.split_last()?;
+    if *last & 0x0f != 0x0f && *last & 0x0f != 0x0c {
+        return None;
+    }
+    let mut x: u64 = 0;
+    for digit in lead.iter().flat_map(|b| vec![b >> 4, b & 0x0f],).chain(Some(last >> 4,),) {
+        if digit > 9 {
+            return None;
+        }
+        x = x.checked_mul(10,)?.checked_add(u64::from(digit,),)?;
+    }
+    Some(x,)
+}
+
+/// Finds the value of a positive zoned decimal number, as
+/// in EBCDIC text
+///
+/// Each byte is a digit, *F0* to *F9*, except that the zone
+/// of the last byte is the sign: *F* or *C*.
+pub fn unzone(zoned: &[u8]) -> Option<u64,> {
+    let (last, lead,) = zoned.split_last()?;
+    if *last >> 4 != 0x0f && *last >> 4 != 0x0c || lead.iter().any(|b| b >> 4 != 0x0f,) {
+        return None;
+    }
+    let mut x: u64 = 0;
+    for digit in zoned.iter().map(|b| b & 0x0f,) {
+        if digit > 9 {
+            return None;
+        }
+        x = x.checked_mul(10,)?.checked_add(u64::from(digit,),)?;
+    }
+    Some(x,)
+}
+
+/// Finds a date from the eight hex digits of a packed
+/// decimal date
+pub fn packed_date(hex: &str, format: PackedFormat,) -> Option<NaiveDate,> {
+    if hex.len() != 8 || !hex.bytes().all(|b| b.is_ascii_hexdigit(),) {
+        return None;
+    }
+    let hex = hex.to_uppercase();
+    let signed = hex.ends_with('F',) || hex.ends_with('C',);
+    let format = match format {
+        PackedFormat::Auto if signed && (hex.starts_with("00",) || hex.starts_with("01",)) => PackedFormat::Cyyddd,
+        PackedFormat::Auto if signed => PackedFormat::Yyyyddd,
+        PackedFormat::Auto => PackedFormat::Yyyymmdd,
+        x => x,
+    };
+    let number = |x: &str| x.parse::<u32>().ok();
+    match format {
+        PackedFormat::Cyyddd if signed => {
+            NaiveDate::from_yo_opt((1900 + number(&hex[1..4],)?) as i32, number(&hex[4..7],)?,)
+        },
+        PackedFormat::Yyyyddd if signed => NaiveDate::from_yo_opt(number(&hex[0..4],)? as i32, number(&hex[4..7],)?,),
+        PackedFormat::Yyyymmdd => {
+            NaiveDate::from_ymd_opt(number(&hex[0..4],)? as i32, number(&hex[4..6],)?, number(&hex[6..8],)?,)
+        },
+        PackedFormat::Mmddyyyy => {
+            NaiveDate::from_ymd_opt(number(&hex[4..8],)? as i32, number(&hex[0..2],)?, number(&hex[2..4],)?,)
+        },
+        PackedFormat::Ddmmyyyy => {
+            NaiveDate::from_ymd_opt(number(&hex[4..8],)? as i32, number(&hex[2..4],)?, number(&hex[0..2],)?,)
+        },
+        _ => None,
+    }
+}
+
+/// Finds a time of day from the hex digits of a packed
+/// decimal time: *HHMMSSTH*, optionally followed by *MIJU*
+/// (to the microsecond) and four zeros, as from
+/// `LINKAGE=SYSTEM`
+pub fn packed_time(hex: &str) -> Option<NaiveTime,> {
+    if !(hex.len() == 8 || hex.len() == 12 || hex.len() == 16) || !hex.bytes().all(|b| b.is_ascii_digit(),) {
+        return None;
+    }
+    let number = |x: &str| x.parse::<u32>().ok();
+    let micro = if hex.len() == 8 { number(&hex[6..8],)? * 10_000 } else { number(&hex[6..12],)? };
+    NaiveTime::from_hms_micro_opt(number(&hex[0..2],)?, number(&hex[2..4],)?, number(&hex[4..6],)?, micro,)
+}
+
+/// Finds a date and time from packed decimal hex text:
+/// *DATE[@TIME]*
+pub fn packed_datetime(text: &str, format: PackedFormat,) -> Option<NaiveDateTime,> {
+    let mut parts = text.splitn(2, '@',);
+    let date = packed_date(parts.next()?, format,)?;
+    let time = match parts.next() {
+        Some(x,) => packed_time(x,)?,
+        None => NaiveTime::from_hms(0, 0, 0,),
+    };
+    Some(date.and_time(time,),)
+}
+
+/// Finds a date and time from raw packed decimal bytes, as
+/// stored by the TIME macro: a date (4 bytes), a time and a
+/// date (8 bytes), or the 16-byte `LINKAGE=SYSTEM` area
+pub fn packed_bytes(raw: &[u8], format: PackedFormat,) -> Option<NaiveDateTime,> {
+    let hex = hex_string(raw,);
+    match raw.len() {
+        4 => packed_datetime(&hex, format,),
+        8 => packed_datetime(&format!("{}@{}", &hex[8..16], &hex[0..8]), format,),
+        16 => packed_datetime(&format!("{}@{}", &hex[16..24], &hex[0..16]), format,),
+        _ => None,
+    }
+}
+
+/// Finds a date from zoned decimal bytes: *0CYYDDD* (7
+/// bytes, signed), or the unsigned 8-digit layouts
+///
+/// The digits are read as the packed decimal date they
+/// spell, so the layouts are the same.
+pub fn zoned_bytes(raw: &[u8], format: PackedFormat,) -> Option<NaiveDateTime,> {
+    let digits = format!("{:0width$}", unzone(raw,)?, width = raw.len());
+    let hex = match raw.len() {
+        7 => format!("{}F", digits),
+        8 if raw[7] >> 4 == 0x0f => digits,
+        _ => return None,
+    };
+    packed_date(&hex, format,).map(|x| x.and_hms(0, 0, 0,),)
+}
+
+/// Converts a packed decimal date, or date and time, given
+/// as hex text; or a zoned decimal date, as the hex of its
+/// bytes
+pub fn from_packed(a: &str, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    let zoned = || {
+        let raw: Vec<u8,> = (0..a.len() / 2).filter_map(|i| u8::from_str_radix(a.get(i * 2..i * 2 + 2,)?, 16,).ok(),).collect();
+        if raw.len() * 2 == a.len() { zoned_bytes(&raw, todwork.packed,) } else { None }
+    };
+    todwork.date = match packed_datetime(a, todwork.packed,).or_else(zoned,) {
+        None => return Err(format!("Packed date {:?} is invalid", a),),
+        Some(x,) => x,
+    };
+    from_datetime_value(todwork,)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unzone_reads_digits_and_sign() {
+        assert_eq!(unzone(&[0xf1, 0xf2, 0xf3],), Some(123));
+        assert_eq!(unzone(&[0xf1, 0xf2, 0xc3],), Some(123));
+        assert_eq!(unzone(&[0xf1, 0xf2, 0xd3],), None);
+        assert_eq!(unzone(&[0xf1, 0xc2, 0xf3],), None);
+        assert_eq!(unzone(&[0xf1, 0xfa],), None);
+    }
+
+    #[test]
+    fn zoned_dates_match_packed() {
+        let day = NaiveDate::from_ymd(2017, 1, 1,).and_hms(0, 0, 0,);
+        let cyyddd = [0xf0, 0xf1, 0xf1, 0xf7, 0xf0, 0xf0, 0xf1];
+        let yyyymmdd = [0xf2, 0xf0, 0xf1, 0xf7, 0xf0, 0xf1, 0xf0, 0xf1];
+        assert_eq!(zoned_bytes(&cyyddd, PackedFormat::Auto,), Some(day));
+        assert_eq!(zoned_bytes(&yyyymmdd, PackedFormat::Auto,), Some(day));
+        assert_eq!(packed_bytes(&[0x01, 0x17, 0x00, 0x1f], PackedFormat::Auto,), Some(day));
+    }
+}
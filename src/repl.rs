@@ -0,0 +1,268 @@
+use super::clockfmt::*;
+use super::todarith::*;
+use super::todinfo::*;
+
+use std::io::{self, BufRead, Write};
+
+/// The help, before and after the registered clock formats
+const HELP: [&str; 2] = [
+    "\
+Values are converted with the current settings. Commands:
+  :tod :date :pmc :unix        convert from TOD, date/time, PMC or Unix values
+  :packed                      convert from packed decimal dates (DATE[@TIME])",
+    "  :utc :tai :lor               set the clock discipline
+  :lzone OFFSET|off            set or drop the local timezone ([-+]n.n)
+  :azone OFFSET|off            set or drop the alternate timezone ([-+]n.n)
+  :zulu [on|off]               show or suppress the Zulu timezone
+  :lpad :rpad :ipad            pad TOD values on the left, right, or intelligently
+  :csv [on|off]                output in CSV format
+  :show                        show the settings
+  :history  !N  !!             list earlier lines, or repeat one
+  :diff                        elapsed time between the last two results
+  :quit                        end the session",
+];
+
+/// An interactive session: the settings that can change
+/// during it, and what has happened so far
+#[derive(Debug)]
+pub struct Session {
+    pub history: Vec<String,>,
+    pub last:    Vec<Tod,>,
+    pub zulu:    bool,
+    pub lzone:   Toffset,
+    pub azone:   Toffset,
+}
+
+impl Session {
+    /// Starts a session with the timezones in the work area
+    pub fn new(todwork: &TodInfo) -> Session {
+        Session {
+            history: Vec::new(),
+            last:    Vec::new(),
+            zulu:    todwork.goff != Toffset(None,),
+            lzone:   todwork.loff,
+            azone:   todwork.aoff,
+        }
+    }
+
+    /// Handles one line of input: either a command, or values
+    /// to convert
+    ///
+    /// Gives the lines of output, or *None* at the end of the
+    /// session
+    pub fn line(&mut self, line: &str, todwork: &mut TodInfo,) -> Option<Vec<String,>,> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Some(Vec::new(),);
+        }
+        if let Some(n,) = line.strip_prefix('!',) {
+            let entry = if n == "!" {
+                self.history.last().cloned()
+            } else {
+                n.parse::<usize>().ok().and_then(|n| self.history.get(n.wrapping_sub(1,),).cloned(),)
+            };
+            return match entry {
+                Some(x,) => {
+                    let mut result = vec![x.clone()];
+                    result.append(&mut self.line(&x, todwork,)?,);
+                    Some(result,)
+                },
+                None => Some(vec![format!("No such line: {}", line)],),
+            };
+        }
+        self.history.push(line.to_string(),);
+        if line.starts_with(':',) {
+            return self.command(line, todwork,);
+        }
+        let mut result: Vec<String,> = Vec::new();
+        for a in line.split_whitespace() {
+            match convert(a, todwork,) {
+                Ok(mut x,) => {
+                    result.append(&mut x,);
+                    self.last.push(todwork.tod,);
+                    if self.last.len() > 2 {
+                        self.last.remove(0,);
+                    }
+                },
+                Err(e,) => result.push(e,),
+            }
+        }
+        Some(result,)
+    }
+
+    /// Carries out a command
+    fn command(&mut self, line: &str, todwork: &mut TodInfo,) -> Option<Vec<String,>,> {
+        let mut words = line.split_whitespace();
+        let cmd = words.next().unwrap_or("",);
+        let arg = words.next();
+        match (cmd, arg,) {
+            (":quit", _,) | (":q", _,) | (":exit", _,) => return None,
+            (":help", _,) | (":h", _,) | (":?", _,) => {
+                let mut help = vec![HELP[0].to_string()];
+                help.extend(FORMATS.iter().map(|x| format!("  {:<29}convert from {}", format!(":{}", x.name()), x.about()),),);
+                help.push(HELP[1].to_string(),);
+                return Some(help,);
+            },
+            (":tod", None,) => todwork.runtype = TodCalc::FromTod,
+            (":date", None,) => todwork.runtype = TodCalc::FromDateTime,
+            (":pmc", None,) => todwork.runtype = TodCalc::FromPMC,
+            (":unix", None,) => todwork.runtype = TodCalc::FromUnix,
+            (":packed", None,) => todwork.runtype = TodCalc::FromPacked,
+            (":utc", None,) => todwork.set_scale("UTC",),
+            (":tai", None,) => todwork.set_scale("TAI",),
+            (":lor", None,) | (":loran", None,) => todwork.set_scale("LOR",),
+            (":lpad", None,) => todwork.pad = Padding::Left,
+            (":rpad", None,) => todwork.pad = Padding::Right,
+            (":ipad", None,) => todwork.pad = Padding::None,
+            (":csv", None,) | (":csv", Some("on",),) => todwork.csv = true,
+            (":csv", Some("off",),) => todwork.csv = false,
+            (":zulu", None,) => self.zulu = !self.zulu,
+            (":zulu", Some("on",),) => self.zulu = true,
+            (":zulu", Some("off",),) => self.zulu = false,
+            (":lzone", Some(x,),) | (":azone", Some(x,),) => {
+                let off = if x == "off" {
+                    Toffset(None,)
+                } else {
+                    match Toffset::new_from_hours(x,) {
+                        Some(x,) => x,
+                        None => return Some(vec![format!("Invalid offset: {}", x)],),
+                    }
+                };
+                if cmd == ":lzone" {
+                    self.lzone = off;
+                    todwork.labels[1] = None;
+                } else {
+                    self.azone = off;
+                    todwork.labels[2] = None;
+                }
+            },
+            (":show", None,) => return Some(vec![self.show(todwork,)],),
+            (":history", None,) => {
+                let n = self.history.len() - 1;
+                return Some(
+                    self.history[..n].iter().enumerate().map(|(i, x,)| format!("{:4}  {}", i + 1, x),).collect(),
+                );
+            },
+            (":diff", None,) => {
+                return Some(vec![match self.last.len() {
+                    2 => diff_text(self.last[0], self.last[1], todwork,),
+                    _ => "Two results are needed for :diff".to_string(),
+                }],);
+            },
+            (x, None,) if find_format(&x[1..],).is_some() => todwork.runtype = TodCalc::FromClock(find_format(&x[1..],).unwrap(),),
+            _ => return Some(vec![format!("Unknown command: {} (try :help)", line)],),
+        }
+        if cmd == ":zulu" || cmd == ":lzone" || cmd == ":azone" {
+            if !self.zulu && self.lzone == Toffset(None,) && self.azone == Toffset(None,) {
+                self.zulu = true;
+                return Some(vec!["No other timezone: Zulu kept".to_string()],);
+            }
+            todwork.set_zones(self.zulu, self.lzone, self.azone,);
+        }
+        Some(Vec::new(),)
+    }
+
+    /// Describes the settings
+    fn show(&self, todwork: &TodInfo,) -> String {
+        let mode = match todwork.runtype {
+            TodCalc::FromTod => "TOD",
+            TodCalc::FromDateTime => "date/time",
+            TodCalc::FromPMC => "PMC",
+            TodCalc::FromUnix => "Unix",
+            TodCalc::FromPacked => "packed decimal",
+            TodCalc::FromClock(x,) => x.heading(),
+        };
+        let pad = match todwork.pad {
+            Padding::Left => "left",
+            Padding::Right => "right",
+            Padding::None => "intelligent",
+        };
+        let zones: Vec<String,> = [todwork.goff, todwork.loff, todwork.aoff]
+            .iter()
+            .enumerate()
+            .filter(|x| (x.1).0.is_some(),)
+            .map(|(slot, x,)| todwork.zone(slot, *x,),)
+            .collect();
+        format!("From {}, zones {}, padding {}{}", mode, zones.join(" ",), pad, if todwork.csv { ", CSV" } else { "" })
+    }
+}
+
+/// Runs an interactive session, reading lines until the end
+/// of the input or `:quit`
+///
+/// The prompt is only wanted when the input is a terminal
+pub fn interactive<R: BufRead, W: Write,>(input: R, mut output: W, prompt: bool, todwork: &mut TodInfo,) -> io::Result<(),> {
+    let mut session = Session::new(todwork,);
+    let mut lines = input.lines();
+    loop {
+        if prompt {
+            write!(output, "untod> ")?;
+            output.flush()?;
+        }
+        let line = match lines.next() {
+            Some(x,) => x?,
+            None => break,
+        };
+        match session.line(&line, todwork,) {
+            Some(x,) => {
+                for l in x {
+                    writeln!(output, "{}", l)?;
+                }
+            },
+            None => break,
+        }
+    }
+    if prompt {
+        writeln!(output)?;
+    }
+    Ok((),)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    /// Runs a session on some input, giving the output
+    fn session(input: &str) -> String {
+        let mut todwork = TodInfo::new();
+        todwork.set_zones(true, Toffset(None,), Toffset(None,),);
+        let mut output: Vec<u8,> = Vec::new();
+        interactive(Cursor::new(input,), &mut output, false, &mut todwork,).unwrap();
+        String::from_utf8(output,).unwrap()
+    }
+
+    #[test]
+    fn converts_with_the_current_settings() {
+        let x = session("d1e0d68173cc0000\n:tai\nd1e0d68173cc0000\n:quit\nd1e0d68173cc0000\n",);
+        let lines: Vec<&str,> = x.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("2017-01-01 00:00:00.000000 UTC+00:00",));
+        assert!(lines[1].contains("2017-01-01 00:00:37.000000 TAI+00:00",));
+    }
+
+    #[test]
+    fn diff_uses_the_values_entered() {
+        let x = session(":date\n2017-01-01\n:lzone 2\n2017-01-01@01\n:diff\n",);
+        assert!(x.lines().last().unwrap().contains(" +3600000000 us ",), "{}", x);
+        assert!(session(":diff\n",).starts_with("Two results are needed",));
+    }
+
+    #[test]
+    fn shows_settings_and_history() {
+        let x = session(":lzone 1\n:csv\n:show\n:history\n!1\n!9\n",);
+        let lines: Vec<&str,> = x.lines().collect();
+        assert_eq!(lines[0], "From TOD, zones UTC+00:00 UTC+01:00, padding intelligent, CSV");
+        assert_eq!(&lines[1..4], &["   1  :lzone 1", "   2  :csv", "   3  :show"]);
+        assert_eq!(lines[4], ":lzone 1");
+        assert_eq!(lines[5], "No such line: !9");
+    }
+
+    #[test]
+    fn reports_bad_commands() {
+        let x = session(":frob\n:lzone east\n:zulu off\n:zulu off\n",);
+        let lines: Vec<&str,> = x.lines().collect();
+        assert_eq!(lines, vec!["Unknown command: :frob (try :help)", "Invalid offset: east", "No other timezone: Zulu kept", "No other timezone: Zulu kept"]);
+    }
+}
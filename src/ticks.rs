@@ -0,0 +1,121 @@
+extern crate chrono;
+use self::chrono::{NaiveDate, NaiveDateTime};
+
+use super::clockfmt::*;
+
+/// Defines the counters kept by other platforms, none of
+/// which allow for leap seconds:
+/// *   Filetime: Windows FILETIME, 100ns units since
+///     1601-01-01
+/// *   Ticks: .NET `DateTime.Ticks`, 100ns units since
+///     0001-01-01
+/// *   JavaMillis: Java `System.currentTimeMillis()`,
+///     milliseconds since 1970-01-01
+/// *   Vms: OpenVMS system time, 100ns units since
+///     1858-11-17
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Counter {
+    Filetime,
+    Ticks,
+    JavaMillis,
+    Vms,
+}
+
+impl ClockFormat for Counter {
+    fn name(&self) -> &'static str {
+        match *self {
+            Counter::Filetime => "filetime",
+            Counter::Ticks => "ticks",
+            Counter::JavaMillis => "java",
+            Counter::Vms => "vms",
+        }
+    }
+
+    fn about(&self) -> &'static str {
+        match *self {
+            Counter::Filetime => "Windows FILETIME values (100ns units since 1601-01-01)",
+            Counter::Ticks => ".NET DateTime.Ticks values (100ns units since 0001-01-01)",
+            Counter::JavaMillis => "Java epoch milliseconds (since 1970-01-01)",
+            Counter::Vms => "OpenVMS system times (100ns units since 1858-11-17)",
+        }
+    }
+
+    fn heading(&self) -> &'static str {
+        match *self {
+            Counter::Filetime => "FILETIME",
+            Counter::Ticks => ".NET Ticks",
+            Counter::JavaMillis => "Java Millis",
+            Counter::Vms => "VMS Time",
+        }
+    }
+
+    fn width(&self) -> usize {
+        match *self {
+            Counter::JavaMillis => 13,
+            _ => 18,
+        }
+    }
+
+    fn epoch(&self) -> NaiveDateTime {
+        match *self {
+            Counter::Filetime => NaiveDate::from_ymd(1601, 1, 1,),
+            Counter::Ticks => NaiveDate::from_ymd(1, 1, 1,),
+            Counter::JavaMillis => NaiveDate::from_ymd(1970, 1, 1,),
+            Counter::Vms => NaiveDate::from_ymd(1858, 11, 17,),
+        }
+        .and_hms(0, 0, 0,)
+    }
+
+    fn resolution(&self) -> i128 {
+        match *self {
+            Counter::JavaMillis => 1_000_000,
+            _ => 100,
+        }
+    }
+
+    /// Java millis may be before the epoch; the others are
+    /// shown only from theirs
+    fn range(&self) -> (i128, i128,) {
+        match *self {
+            Counter::JavaMillis => (i128::from(i64::MIN,), i128::from(i64::MAX,),),
+            _ => (0, i128::from(i64::MAX,),),
+        }
+    }
+
+    /// Reads a decimal count, or a hex one starting with
+    /// *0x*
+    fn parse(&self, text: &str,) -> Option<i128,> {
+        match text.strip_prefix("0x",).or_else(|| text.strip_prefix("0X",),) {
+            Some(x,) => i64::from_str_radix(x, 16,).ok().map(i128::from,),
+            None => text.parse::<i64>().ok().map(i128::from,),
+        }
+    }
+
+    fn format(&self, count: i128,) -> String { count.to_string() }
+
+    fn pattern(&self) -> &'static str {
+        match *self {
+            Counter::Filetime | Counter::Ticks => r"\b[0-9]{17,18}\b",
+            Counter::JavaMillis => r"\b[0-9]{12,13}\b",
+            Counter::Vms => r"\b[0-9]{17}\b",
+        }
+    }
+
+    /// Reads a raw 8-byte field, as each platform stores it:
+    /// FILETIME, VMS time and .NET ticks (from
+    /// `DateTime.ToBinary`, without the kind) little-endian,
+    /// Java millis big-endian
+    fn count_from_bytes(&self, raw: &[u8],) -> Option<i128,> {
+        let mut x = [0u8; 8];
+        if raw.len() != 8 {
+            return None;
+        }
+        x.copy_from_slice(raw,);
+        let count = match *self {
+            Counter::Filetime | Counter::Vms => i64::from_le_bytes(x,),
+            Counter::Ticks => i64::from_le_bytes(x,) & 0x3fff_ffff_ffff_ffff,
+            Counter::JavaMillis => i64::from_be_bytes(x,),
+        };
+        Some(i128::from(count,),)
+    }
+}
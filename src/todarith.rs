@@ -0,0 +1,352 @@
+extern crate chrono;
+use self::chrono::{Duration, NaiveDateTime};
+
+use super::todinfo::*;
+
+use std::fmt;
+
+/// Signed time interval
+///
+/// A number of microseconds, which may be negative
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval(pub i64,);
+impl Interval {
+    /// Makes an interval from a number of microseconds
+    pub fn new(tval: i64) -> Interval { Interval(tval,) }
+
+    /// Makes an interval from a string, or *None* if it
+    /// can't be understood
+    ///
+    /// The string can be either a series of numbers with
+    /// units (`1h30m`, `2.5s`, `250ms`, `1d12h`) or a clock
+    /// form (`01:30:00.000250`, `90:00`). Units are `d`,
+    /// `h`, `m`, `s`, `ms`, `us` (or `µs`); a bare number
+    /// is seconds. A leading sign is allowed.
+    pub fn new_from_str(ival: &str) -> Option<Interval,> {
+        let (neg, body,) = match ival.chars().next() {
+            Some('-',) => (true, &ival[1..],),
+            Some('+',) => (false, &ival[1..],),
+            _ => (false, ival,),
+        };
+        if body.is_empty() {
+            return None;
+        }
+        let micros = if body.contains(':',) {
+            clock_micros(body,)?
+        } else {
+            unit_micros(body,)?
+        };
+        Some(Interval(if neg { -micros } else { micros },),)
+    }
+
+    /// The interval in whole and fractional seconds, signed
+    /// only if negative
+    pub fn seconds(&self) -> String {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let x = self.0.abs();
+        format!("{}{}.{:06}", sign, x / 1_000_000, x % 1_000_000)
+    }
+}
+
+impl fmt::Display for Interval {
+    /// Displays as `[-+]hh:mm:ss.ffffff`, with hours
+    /// continuing past 24
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "+" };
+        let x = self.0.abs();
+        let xs = x / 1_000_000;
+        write!(
+            f,
+            "{}{:02}:{:02}:{:02}.{:06}",
+            sign,
+            xs / 3600,
+            xs / 60 % 60,
+            xs % 60,
+            x % 1_000_000
+        )
+    }
+}
+
+/// Scales a decimal number (possibly with a fraction) by a
+/// number of microseconds per unit
+fn scaled(num: &str, unit: i64,) -> Option<i64,> {
+    if num.is_empty() || num == "." {
+        return None;
+    }
+    let (ipart, fpart,) = match num.find('.',) {
+        Some(x,) => (&num[..x], &num[x + 1..],),
+        None => (num, "",),
+    };
+    if !ipart.bytes().all(|b| b.is_ascii_digit(),) || !fpart.bytes().all(|b| b.is_ascii_digit(),) {
+        return None;
+    }
+    let whole = if ipart.is_empty() { 0 } else { ipart.parse::<i64>().ok()? };
+    let whole = whole.checked_mul(unit,)?;
+    let fpart = &fpart[..fpart.len().min(12,)];
+    if fpart.is_empty() {
+        return Some(whole,);
+    }
+    let frac = fpart.parse::<i128>().ok()? * i128::from(unit,) / 10i128.pow(fpart.len() as u32,);
+    whole.checked_add(frac as i64,)
+}
+
+/// Interprets a series of numbers with units
+fn unit_micros(body: &str) -> Option<i64,> {
+    if let Some(x,) = scaled(body, 1_000_000,) {
+        return Some(x,);
+    }
+    let mut total: i64 = 0;
+    let mut rest = body;
+    while !rest.is_empty() {
+        let nlen = rest.find(|c: char| !(c.is_ascii_digit() || c == '.'),)?;
+        let (num, tail,) = rest.split_at(nlen,);
+        let ulen = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.',)
+            .unwrap_or(tail.len(),);
+        let (unit, tail,) = tail.split_at(ulen,);
+        let unit = match unit {
+            "d" => 86_400_000_000,
+            "h" => 3_600_000_000,
+            "m" => 60_000_000,
+            "s" => 1_000_000,
+            "ms" => 1_000,
+            "us" | "µs" => 1,
+            _ => return None,
+        };
+        total = total.checked_add(scaled(num, unit,)?,)?;
+        rest = tail;
+    }
+    Some(total,)
+}
+
+/// Interprets a clock form: `[[h:]m:]s[.f]`
+fn clock_micros(body: &str) -> Option<i64,> {
+    let parts: Vec<&str,> = body.split(':',).collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let (last, lead,) = parts.split_last()?;
+    let mut total: i64 = 0;
+    for part in lead {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit(),) {
+            return None;
+        }
+        total = total.checked_mul(60,)?.checked_add(part.parse::<i64>().ok()?,)?;
+    }
+    total.checked_mul(60_000_000,)?.checked_add(scaled(last, 1_000_000,)?,)
+}
+
+/// Finds the number of leap seconds in effect at a TOD
+/// Clock value
+fn leaps_at(tod: Tod, todwork: &mut TodInfo,) -> i64 {
+    todwork.tod = tod;
+    todwork.lstab.ls_search_tod(todwork,)
+}
+
+/// Calculates the elapsed time between two TOD Clock values
+///
+/// Reports microseconds, seconds and *hh:mm:ss*; under UTC
+/// also the number of leap seconds inserted in between,
+/// which are included in the elapsed time
+pub fn tod_diff(a: &str, b: &str, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    let atod = match Tod::new_from_hex(a, &todwork.pad,) {
+        None => return Err(format!("TOD value is invalid: {:?}", a),),
+        Some(x,) => x,
+    };
+    let btod = match Tod::new_from_hex(b, &todwork.pad,) {
+        None => return Err(format!("TOD value is invalid: {:?}", b),),
+        Some(x,) => x,
+    };
+    Ok(vec![diff_text(atod, btod, todwork,)],)
+}
+
+/// Gives the column headers for elapsed times, with the
+/// leap seconds only under UTC
+pub fn diff_headers(todwork: &TodInfo) -> Vec<String,> {
+    let leap = if todwork.utc { " Leap" } else { "" };
+    if todwork.csv {
+        return vec![format!("FromTOD,ToTOD,Micros,Seconds,Elapsed{}", leap.replace(' ', ",",))];
+    }
+    let title = format!("{:^21} {:2} {:^21}   {:>16} {:>18} {:>18}{}", "From TOD", "", "To TOD", "Micros", "Seconds", "Elapsed", leap);
+    let rule = format!("{} -- {} : {} {} {}{}", "-".repeat(21,), "-".repeat(21,), "-".repeat(16,), "-".repeat(18,), "-".repeat(18,), if todwork.utc { " ----" } else { "" });
+    vec![title, rule]
+}
+
+/// Formats the elapsed time between two TOD Clock values as
+/// a line of text
+///
+/// In text, the values are signed and lined up under the
+/// headers; in CSV, only negative values are signed.
+pub fn diff_text(atod: Tod, btod: Tod, todwork: &mut TodInfo,) -> String {
+    let elapsed = Interval(btod.0 as i64 - atod.0 as i64,);
+    let leaps = leaps_at(btod, todwork,) - leaps_at(atod, todwork,);
+    if todwork.csv {
+        let clock = elapsed.to_string();
+        let clock = clock.trim_start_matches('+',);
+        if todwork.utc {
+            format!("{},{},{},{},{},*{:+}",
+            atod, btod, elapsed.0, elapsed.seconds(), clock, leaps
+            )
+        } else {
+            format!("{},{},{},{},{}",
+            atod, btod, elapsed.0, elapsed.seconds(), clock
+            )
+        }
+    } else {
+        let seconds = if elapsed.0 < 0 { elapsed.seconds() } else { format!("+{}", elapsed.seconds()) };
+        let line = format!("{} to {} : {:>+13} us {:>16} s {:>18}",
+        atod, btod, elapsed.0, seconds, elapsed
+        );
+        if todwork.utc { format!("{} {:>4}", line, format!("*{:+}", leaps)) } else { line }
+    }
+}
+
+/// Adds an interval to (or, if *negate*, subtracts it from)
+/// a TOD Clock value, and converts the result
+///
+/// The TOD Clock counts leap seconds, so the result is
+/// correct across a leap second under UTC
+pub fn tod_add(a: &str, ival: &str, negate: bool, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    let atod = match Tod::new_from_hex(a, &todwork.pad,) {
+        None => return Err(format!("TOD value is invalid: {:?}", a),),
+        Some(x,) => x,
+    };
+    let delta = match Interval::new_from_str(ival,) {
+        None => return Err(format!("Interval is invalid: {:?}", ival),),
+        Some(x,) => if negate { -x.0 } else { x.0 },
+    };
+    match (atod.0 as i64).checked_add(delta,) {
+        Some(x,) if (0..1 << 60).contains(&x,) => {
+            todwork.tod = Tod(x as u64,);
+            from_tod_value(todwork,)
+        },
+        _ => Err(format!("Result is out of range: {} {}", a, ival),),
+    }
+}
+
+/// The values for every step from a starting date/time to
+/// an ending one (inclusive), each converted as it is
+/// reached
+///
+/// The steps are taken in calendar time, so an hourly range
+/// lands on every hour boundary even across a leap second
+pub struct DateRange<'a,> {
+    todwork: &'a mut TodInfo,
+    next:    Option<NaiveDateTime,>,
+    end:     NaiveDateTime,
+    step:    Duration,
+}
+
+impl<'a,> Iterator for DateRange<'a,> {
+    type Item = Result<Vec<String,>, String,>;
+
+    /// Converts the next step: its lines, or why it couldn't
+    /// be converted
+    fn next(&mut self) -> Option<Self::Item,> {
+        let xdate = self.next.filter(|x| *x <= self.end,)?;
+        self.next = xdate.checked_add_signed(self.step,);
+        self.todwork.date = xdate;
+        Some(from_datetime_value(self.todwork,),)
+    }
+}
+
+/// Starts a range of dates/times to convert, or describes
+/// why it can't be: a bad date or step, or an end before
+/// the start
+pub fn date_range<'a,>(start: &str, end: &str, step: &str, todwork: &'a mut TodInfo,) -> Result<DateRange<'a,>, String,> {
+    let sdate = match finddate(start.to_string(),) {
+        Err(_,) => return Err(format!("Date {:?} is invalid", start),),
+        Ok(x,) => x,
+    };
+    let edate = match finddate(end.to_string(),) {
+        Err(_,) => return Err(format!("Date {:?} is invalid", end),),
+        Ok(x,) => x,
+    };
+    if edate < sdate {
+        return Err(format!("Range ends before it starts: {} {}", start, end),);
+    }
+    let step = match Interval::new_from_str(step,) {
+        Some(x,) if x.0 > 0 => Duration::microseconds(x.0,),
+        _ => return Err(format!("Interval is invalid: {:?}", step),),
+    };
+    Ok(DateRange { todwork, next: Some(sdate,), end: edate, step },)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_with_units() {
+        assert_eq!(Interval::new_from_str("1h30m",), Some(Interval(5_400_000_000,)));
+        assert_eq!(Interval::new_from_str("2.5s",), Some(Interval(2_500_000,)));
+        assert_eq!(Interval::new_from_str("1d12h",), Some(Interval(129_600_000_000,)));
+        assert_eq!(Interval::new_from_str("250ms",), Some(Interval(250_000,)));
+        assert_eq!(Interval::new_from_str("7us",), Some(Interval(7,)));
+        assert_eq!(Interval::new_from_str("42",), Some(Interval(42_000_000,)));
+        assert_eq!(Interval::new_from_str("5y",), None);
+        assert_eq!(Interval::new_from_str("",), None);
+    }
+
+    #[test]
+    fn intervals_in_clock_form() {
+        assert_eq!(Interval::new_from_str("01:30:00.000250",), Some(Interval(5_400_000_250,)));
+        assert_eq!(Interval::new_from_str("90:00",), Some(Interval(5_400_000_000,)));
+        assert_eq!(Interval::new_from_str("1:2:3:4",), None);
+        assert_eq!(Interval::new_from_str("1::00",), None);
+    }
+
+    #[test]
+    fn intervals_with_signs_and_overflow() {
+        assert_eq!(Interval::new_from_str("-1m",), Some(Interval(-60_000_000,)));
+        assert_eq!(Interval::new_from_str("+1m",), Some(Interval(60_000_000,)));
+        assert_eq!(Interval::new_from_str("-",), None);
+        assert_eq!(Interval::new_from_str("999999999999d",), None);
+        assert_eq!(Interval::new_from_str("9223372036854775807s",), None);
+    }
+
+    #[test]
+    fn diff_across_a_leap_second() {
+        // 2016-12-31 23:00:01 to 2017-01-01 00:00:00, UTC
+        let mut todwork = TodInfo::new();
+        todwork.csv = true;
+        let x = tod_diff("d1e0c918398c0000", "d1e0d68173cc0000", &mut todwork,).unwrap();
+        assert_eq!(x, vec!["000 d1e0c918 398c0---,000 d1e0d681 73cc0---,3600000000,3600.000000,01:00:00.000000,*+1"]);
+    }
+
+    #[test]
+    fn add_out_of_range() {
+        let mut todwork = TodInfo::new();
+        assert!(tod_add("0", "1s", true, &mut todwork,).is_err());
+        assert!(tod_add("d1e0d68173cc0000", "36500000d", false, &mut todwork,).is_err());
+        assert!(tod_add("d1e0d68173cc0000", "1x", false, &mut todwork,).is_err());
+        assert!(tod_add("d1e0d68173cc0000", "1s", false, &mut todwork,).is_ok());
+    }
+
+    #[test]
+    fn range_steps_through_the_end() {
+        let mut todwork = TodInfo::new();
+        todwork.brief = true;
+        todwork.runtype = TodCalc::FromDateTime;
+        let steps: Vec<_,> = date_range("2016-12-31@23:00", "2017-01-01", "30m", &mut todwork,).unwrap().collect();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[2], Ok(vec!["000 d1e0d681 73cc0---".to_string(); 2]));
+    }
+
+    #[test]
+    fn range_reports_each_failing_step() {
+        let mut todwork = TodInfo::new();
+        todwork.loff = Toffset(Some(-7200,),);
+        let steps: Vec<_,> = date_range("1900-01-01@00:00", "1900-01-01@02:00", "1h", &mut todwork,).unwrap().collect();
+        assert_eq!(steps.len(), 3);
+        assert!(steps[0].is_err() && steps[1].is_err() && steps[2].is_ok());
+    }
+
+    #[test]
+    fn range_must_run_forwards() {
+        let mut todwork = TodInfo::new();
+        assert!(date_range("2017-01-02", "2017-01-01", "1h", &mut todwork,).is_err());
+        assert!(date_range("2017-01-01", "2017-01-02", "-1h", &mut todwork,).is_err());
+        assert_eq!(date_range("2017-01-01", "2017-01-01", "1h", &mut todwork,).unwrap().count(), 1);
+    }
+}
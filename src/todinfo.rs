@@ -4,23 +4,25 @@ use self::clap::ArgMatches;
 extern crate chrono;
 use self::chrono::{Duration, Local, NaiveDate, NaiveDateTime, Offset, ParseResult, Utc};
 
-extern crate clipboard;
-use self::clipboard::{ClipboardContext, ClipboardProvider};
-
+use super::args::{runtype, value};
+use super::clockfmt::*;
+use super::config::*;
 use super::leapsectab::*;
+use super::packed::*;
 
 use std::cmp::min;
-use std::num::ParseIntError;
+use std::io;
 use std::fmt;
-use std::u32::MAX;
-use std::fs::File;
-use std::io::{self, Read};
 
 /// Defines calculation type (input value interpretation):
 /// *    FromTod: Inputs are (hex) TOD Clock values
 /// *   FromDateTime: Inputs are Date/Time values
 /// *   FromPMC: Inputs are (hex) Permetual Minute Clock
-/// values
+///     values
+/// *   FromPacked: Inputs are (hex) packed decimal dates,
+///     with or without times
+/// *   FromClock: Inputs are values in a registered clock
+///     format
 
 #[derive(Clone, Copy, Debug)]
 pub enum TodCalc {
@@ -28,16 +30,18 @@ pub enum TodCalc {
     FromDateTime,
     FromPMC,
     FromUnix,
+    FromPacked,
+    FromClock(&'static dyn ClockFormat,),
 }
 
 /// Defines type of padding for input TOD Clock values
 /// * Left: Pad with zeros on the left
 /// * Right: Pad with zeros on the right
 /// * None: "Intelligent" padding
-/// <br/> If first digit is 0-b,
-/// pad with two zero digits on the left,
-/// then fill with zeros on the right.
-/// <br/>If c-f, pad with three zeros.
+///   <br/> If first digit is 0-b,
+///   pad with two zero digits on the left,
+///   then fill with zeros on the right.
+///   <br/>If c-f, pad with three zeros.
 
 #[derive(Clone, Copy, Debug)]
 pub enum Padding {
@@ -62,6 +66,8 @@ pub struct TodInfo {
     pub date:    NaiveDateTime,
     pub pmc:     PerpMinuteClock,
     pub usc:     UnixSecondsClock,
+    pub unix:    UnixUnit,
+    pub unixout: UnixUnit,
     pub goff:    Toffset,
     pub loff:    Toffset,
     pub aoff:    Toffset,
@@ -69,21 +75,33 @@ pub struct TodInfo {
     pub src:     Source,
     pub cname:   String,
     pub csv:     bool,
+    pub brief:   bool,
+    pub template: Option<String,>,
+    pub packed:  PackedFormat,
+    pub labels:  [Option<String,>; 3],
+    pub columns: Vec<&'static dyn ClockFormat,>,
+    pub excel1904: bool,
     pub utc:     bool,
     pub tai:     i64,
     pub lsec:    i64,
     pub lstab:   LeapSecTable,
 }
 
+impl Default for TodInfo {
+    fn default() -> TodInfo { TodInfo::new() }
+}
+
 impl TodInfo {
     /// Makes a new default (empty) work area
-    fn new() -> TodInfo {
+    pub fn new() -> TodInfo {
         TodInfo {
             runtype: TodCalc::FromTod,
             tod:     Tod(0,),
             date:    Utc::now().naive_utc(),
             pmc:     PerpMinuteClock(None,),
-            usc:     UnixSecondsClock(None,),
+            usc:     UnixSecondsClock(None, 0,),
+            unix:    UnixUnit::Auto,
+            unixout: UnixUnit::Auto,
             goff:    Toffset(Some(0,),),
             loff:    Toffset(Some(0,),),
             aoff:    Toffset(None,),
@@ -91,6 +109,12 @@ impl TodInfo {
             src:     Source::None,
             cname:   "UTC".to_string(),
             csv:     false,
+            brief:   false,
+            template: None,
+            packed:  PackedFormat::Auto,
+            labels:  [None, None, None],
+            columns: Vec::new(),
+            excel1904: false,
             utc:     true,
             tai:     0,
             lsec:    0,
@@ -98,114 +122,247 @@ impl TodInfo {
         }
     }
 
-    /// Builds a new work area from command line arguments
-    pub fn new_from_args(cmdl: &ArgMatches) -> TodInfo {
+    /// Builds a new work area from command line arguments,
+    /// with defaults from the configuration
+    pub fn new_from_args(cmdl: &ArgMatches, config: &Config,) -> Result<TodInfo, ConfigError,> {
         let mut todwork = TodInfo::new();
-        if cmdl.is_present("reverse",) {
-            todwork.runtype = TodCalc::FromDateTime;
-        }
-        if cmdl.is_present("unix",) {
-            todwork.runtype = TodCalc::FromUnix;
-        }
-        if cmdl.is_present("pmc",) {
-            todwork.runtype = TodCalc::FromPMC;
-        }
-        if cmdl.is_present("clipboard",) {
+        todwork.runtype = runtype(cmdl,);
+        if cmdl.get_flag("clipboard",) {
             todwork.src = Source::Clip ;
         }
-        if cmdl.is_present("infile",) {
+        if cmdl.contains_id("infile",) {
             todwork.src = Source::File ;
         }
-        if cmdl.is_present("pl",) {
-            todwork.pad = Padding::Left;
-        }
-        if cmdl.is_present("pr",) {
-            todwork.pad = Padding::Right;
-        }
-        todwork.loff = match cmdl.value_of("zl",) {
-            None => Toffset(Some(i64::from(
-                Local::now().offset().fix().local_minus_utc(),
-            ),),),
-            Some(soff,) => match soff.parse::<f32>() {
-                Ok(noff,) => Toffset(Some((60.0 * noff).round() as i64 * 60,),),
-                _ => {
-                    eprintln!("Invalid offset: --zl {}", soff);
-                    Toffset(None,)
-                },
-            },
+        todwork.pad = if cmdl.get_flag("pl",) {
+            Padding::Left
+        } else if cmdl.get_flag("pr",) {
+            Padding::Right
+        } else {
+            match config.padding.as_deref() {
+                Some("left",) => Padding::Left,
+                Some("right",) => Padding::Right,
+                _ => Padding::None,
+            }
+        };
+        let loff = match value(cmdl, "zl",).or(config.lzone.as_deref(),) {
+            None => Toffset::new_local(),
+            Some(soff,) => Toffset::new_setting("--lzone", soff,),
         };
-        todwork.aoff = match cmdl.value_of("za",) {
+        let aoff = match value(cmdl, "za",).or(config.azone.as_deref(),) {
             None => Toffset(None,),
-            Some(soff,) => match soff.parse::<f32>() {
-                Ok(noff,) => Toffset(Some((60.0 * noff).round() as i64 * 60,),),
-                _ => {
-                    eprintln!("Invalid offset: --zl {}", soff);
-                    Toffset(None,)
-                },
-            },
+            Some(soff,) => Toffset::new_setting("--azone", soff,),
         };
-        if cmdl.is_present("ng",) {
-            if todwork.loff == Toffset(None,) && todwork.aoff == Toffset(None,) {
+        let zulu = cmdl.get_flag("zulu",) || !cmdl.get_flag("ng",) && config.zulu.unwrap_or(true,);
+        todwork.set_zones(zulu, loff, aoff,);
+        if value(cmdl, "zl",).is_none() {
+            todwork.labels[1] = config.llabel.clone();
+        }
+        if value(cmdl, "za",).is_none() {
+            todwork.labels[2] = config.alabel.clone();
+        }
+        if cmdl.get_flag("utc",) {
+            todwork.set_scale("UTC",);
+        } else if cmdl.get_flag("lor",) {
+            todwork.set_scale("LOR",);
+        } else if cmdl.get_flag("tai",) {
+            todwork.set_scale("TAI",);
+        } else if let Some(ref x,) = config.scale {
+            todwork.set_scale(&x.to_uppercase(),);
+        }
+        todwork.csv = cmdl.get_flag("csv",) || !cmdl.get_flag("text",) && config.format.as_deref() == Some("csv",);
+        todwork.brief = cmdl.get_flag("filter",) || cmdl.get_flag("replace",);
+        if let Some(x,) = value(cmdl, "pformat",) {
+            todwork.packed = PackedFormat::new_from_str(x,).unwrap_or(PackedFormat::Auto,);
+        }
+        if let Some(x,) = value(cmdl, "uunit",) {
+            todwork.unix = UnixUnit::new_from_str(x,).unwrap_or(UnixUnit::Auto,);
+        }
+        if let Some(x,) = value(cmdl, "uout",) {
+            todwork.unixout = UnixUnit::new_from_str(x,).unwrap_or(UnixUnit::Auto,);
+        }
+        if cmdl.get_flag("unixms",) {
+            todwork.unix = UnixUnit::Millis;
+            todwork.unixout = UnixUnit::Millis;
+        }
+        todwork.excel1904 = cmdl.get_flag("excel1904",);
+        if let Some(x,) = cmdl.get_many::<String,>("columns",) {
+            todwork.columns = x.filter_map(|x| find_format(x,),).collect();
+        }
+        if !cmdl.get_flag("notemplate",) {
+            todwork.template = value(cmdl, "template",).map(str::to_string,).or_else(|| config.template.clone(),);
+        }
+        if let Some(ref path,) = config.leapsecs {
+            let name = path.display().to_string();
+            todwork.lstab = match LeapSecTable::new_from_file(path,) {
+                Ok(x,) => x,
+                Err(ref e,) if e.kind() == io::ErrorKind::InvalidData => {
+                    return Err(ConfigError::Invalid(name, e.to_string(),),)
+                },
+                Err(e,) => return Err(ConfigError::Open(name, e,),),
+            };
+        }
+        Ok(todwork,)
+    }
+
+    /// Sets the time zones to convert for: Zulu (which can
+    /// only be dropped if there's another), local, and
+    /// alternate. Duplicates are dropped.
+    pub fn set_zones(&mut self, zulu: bool, loff: Toffset, aoff: Toffset,) {
+        self.goff = Toffset(Some(0,),);
+        self.loff = loff;
+        self.aoff = aoff;
+        if !zulu {
+            if self.loff == Toffset(None,) && self.aoff == Toffset(None,) {
                 eprintln!("No other offsets available; --ng ignored.");
             } else {
-                todwork.goff = Toffset(None,);
+                self.goff = Toffset(None,);
             };
         };
-        if cmdl.is_present("lor",) {
-            todwork.utc = false;
-            todwork.cname = "LOR".to_string();
-        }
-        if cmdl.is_present("tai",) {
-            todwork.utc = false;
-            todwork.cname = "TAI".to_string();
-            todwork.tai = -10;
+        if self.aoff == self.goff || self.aoff == self.loff {
+            self.aoff = Toffset(None,);
         }
-        if todwork.aoff == todwork.goff || todwork.aoff == todwork.loff {
-            todwork.aoff = Toffset(None,);
+        if self.loff == self.goff {
+            self.loff = Toffset(None,);
         }
-        if todwork.loff == todwork.goff {
-            todwork.loff = Toffset(None,);
+    }
+
+    /// Sets the clock discipline: *UTC* (with leap seconds),
+    /// *LOR* (LORAN/IBM, without), or *TAI* (without)
+    pub fn set_scale(&mut self, cname: &str,) {
+        match cname {
+            "LOR" => {
+                self.utc = false;
+                self.tai = 0;
+            },
+            "TAI" => {
+                self.utc = false;
+                self.tai = -10;
+            },
+            _ => {
+                self.utc = true;
+                self.tai = 0;
+            },
         }
-        todwork.csv = cmdl.is_present("csv",) ;
-        todwork
+        self.cname = cname.to_string();
     }
 
-    /// Formats the work area values as a line of text
-    pub fn text(&self, offset: Toffset,) -> String {
+    /// Formats the work area values as a line of text, for
+    /// one of the time zones (0 Zulu, 1 local, 2 alternate)
+    ///
+    /// A *brief* line has only the converted value: the TOD
+    /// when converting from date/time, otherwise the date,
+    /// time and zone
+    pub fn text(&self, slot: usize, offset: Toffset,) -> String {
+        let ozone = self.zone(slot, offset,);
+        if self.brief {
+            return match self.runtype {
+                TodCalc::FromDateTime | TodCalc::FromPacked => format!("{}", self.tod),
+                _ => format!("{} {}", self.date.format("%F %H:%M:%S%.6f",), ozone),
+            };
+        }
+        if let Some(ref t,) = self.template {
+            return self.fill(t, &ozone,);
+        }
         let ojd = self.date.format("%Y.%j",);
         let oday = self.date.format("%a",);
-        if self.csv {
+        let line = if self.csv {
             let odate = self.date.format("%F,%H:%M:%S%.6f",);
             if self.utc {
-                format!("{},{},{}{},{},{},{},{:0},*{:+}",
-                self.tod, odate, self.cname, offset, ojd, oday, self.pmc, self.usc_csv(), 
+                format!("{},{},{},{},{},{},{:0},*{:+}",
+                self.tod, odate, ozone, ojd, oday, self.pmc, self.usc_csv(), 
                 self.lsec 
                 )
             } else {
-                format!("{},{},{}{},{},{},{},{:0},NA",
-                self.tod, odate, self.cname, offset, ojd, oday, self.pmc, self.usc_csv()
+                format!("{},{},{},{},{},{},{:0},NA",
+                self.tod, odate, ozone, ojd, oday, self.pmc, self.usc_csv()
                 )
             }    
         } else {
             let odate = self.date.format("%F %H:%M:%S%.6f",);
             if self.utc {
-                format!("{} : {} {}{} {} {} {} {} *{:+}",
-                self.tod, odate, self.cname, offset, ojd, oday, self.pmc, self.usc, self.lsec
+                format!("{} : {} {:9} {} {} {} {:>14} *{:+}",
+                self.tod, odate, ozone, ojd, oday, self.pmc, self.usc_csv(), self.lsec
                 )
             } else {
-                format!("{} : {} {}{} {} {} {} {}",
-                self.tod, odate, self.cname, offset, ojd, oday, self.pmc, self.usc
+                format!("{} : {} {:9} {} {} {} {:>14}",
+                self.tod, odate, ozone, ojd, oday, self.pmc, self.usc_csv()
                 )
             }    
+        };
+        let sep = if self.csv { "," } else { " " };
+        self.columns.iter().fold(line, |line, x| format!("{}{}{}", line, sep, self.column(*x,)),)
+    }
+
+    /// Formats the value for an optional column, or dashes
+    /// if out-of-range; in CSV, unpadded, or *NA*
+    pub fn column(&self, format: &dyn ClockFormat,) -> String {
+        let width = format.width();
+        match format_value(format, self,) {
+            Some(x,) if self.csv => x,
+            Some(x,) => format!("{:>width$}", x, width = width),
+            None if self.csv => "NA".to_string(),
+            None => "-".repeat(width,),
         }
     }
-    
-    pub fn usc_csv(&self) -> String {
-        match self.usc.0 {
-            Some(x,) => format!("{}", x),
-            None     => "--".to_string(),
+
+    /// Gives the column headers for conversion results, with
+    /// any optional columns
+    pub fn headers(&self) -> Vec<String,> {
+        if self.csv {
+            let mut title = "ExtTOD,Date,Time,Zone,Julian,D,Perp,Unix,Leap".to_string();
+            for x in self.columns.iter() {
+                title = format!("{},{}", title, x.heading());
+            }
+            return vec![title];
         }
+        let mut title = "Ext       TOD              Date          Time        Zone     Julian   D    Perp        Unix      Leap".to_string();
+        let mut rule = "--- ----------------- : ---------- --------------- --------- -------- --- -------- -------------- ----".to_string();
+        for x in self.columns.iter() {
+            title = format!("{} {:^width$}", title, x.heading(), width = x.width());
+            rule = format!("{} {}", rule, "-".repeat(x.width(),));
+        }
+        vec![title, rule]
+    }
+
+    /// Names one of the time zones (0 Zulu, 1 local, 2
+    /// alternate): its label, if it has one, or the clock
+    /// discipline and offset
+    ///
+    /// A label stands in for the offset, but not for a TAI or
+    /// LOR discipline, which follows it.
+    pub fn zone(&self, slot: usize, offset: Toffset,) -> String {
+        match self.labels[slot] {
+            Some(ref x,) if self.utc => x.clone(),
+            Some(ref x,) => format!("{} {}", x, self.cname),
+            None => format!("{}{}", self.cname, offset),
+        }
+    }
+
+    /// Fills in an output template
+    ///
+    /// The fields are `{tod}`, `{date}`, `{time}`, `{zone}`,
+    /// `{julian}`, `{day}`, `{pmc}`, `{unix}`, `{leap}`, and
+    /// `{NAME}` for each registered clock format
+    pub fn fill(&self, template: &str, zone: &str,) -> String {
+        let leap = if self.utc { format!("*{:+}", self.lsec) } else { "NA".to_string() };
+        let line = template
+            .replace("{tod}", &self.tod.to_string(),)
+            .replace("{date}", &self.date.format("%F",).to_string(),)
+            .replace("{time}", &self.date.format("%H:%M:%S%.6f",).to_string(),)
+            .replace("{zone}", zone,)
+            .replace("{julian}", &self.date.format("%Y.%j",).to_string(),)
+            .replace("{day}", &self.date.format("%a",).to_string(),)
+            .replace("{pmc}", &self.pmc.to_string(),)
+            .replace("{unix}", &self.usc_csv(),)
+            .replace("{leap}", &leap,);
+        FORMATS.iter().fold(line, |line, x| {
+            let field = format!("{{{}}}", x.name());
+            if line.contains(&field,) { line.replace(&field, self.column(*x,).trim(),) } else { line }
+        },)
     }
+
+    /// Formats the Unix Seconds Clock in the chosen unit
+    pub fn usc_csv(&self) -> String { self.usc.text(self.unixout,) }
 }
 
 /// Time zone offset for a calculation
@@ -213,6 +370,39 @@ impl TodInfo {
 /// Optional, signed, number of seconds
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Toffset(pub Option<i64,>,);
+impl Toffset {
+    /// Makes the offset of the local time zone
+    pub fn new_local() -> Toffset {
+        Toffset(Some(i64::from(Local::now().offset().fix().local_minus_utc(),),),)
+    }
+
+    /// Makes an offset from a setting: a number of hours, or
+    /// *off* for none
+    ///
+    /// An invalid setting is reported, and taken as none
+    pub fn new_setting(name: &str, soff: &str,) -> Toffset {
+        if soff == "off" {
+            return Toffset(None,);
+        }
+        match Toffset::new_from_hours(soff,) {
+            Some(x,) => x,
+            _ => {
+                eprintln!("Invalid offset: {} {}", name, soff);
+                Toffset(None,)
+            },
+        }
+    }
+
+    /// Makes an offset from a number of hours (*[-+]n.n*),
+    /// rounded to the minute
+    pub fn new_from_hours(hours: &str) -> Option<Toffset,> {
+        match hours.parse::<f32>() {
+            Ok(noff,) => Some(Toffset(Some((60.0 * noff).round() as i64 * 60,),),),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for Toffset {
     /// Displays as *+hh:mm*
     fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
@@ -266,33 +456,124 @@ impl fmt::Display for PerpMinuteClock {
     }
 }
 
+/// Defines the unit of Unix time values
+/// *   Auto: On input, found from the number of digits (up
+///     to 11 for seconds, 14 for milliseconds, 17 for
+///     microseconds, and nanoseconds beyond); on output,
+///     seconds, with the fraction (to the microsecond) if
+///     there is one
+/// *   Seconds: Seconds, with any fraction (shown to the
+///     microsecond)
+/// *   Millis, Micros, Nanos: Milli-, micro- or
+///     nanoseconds (shown whole)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnixUnit {
+    Auto,
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl UnixUnit {
+    /// Finds the unit from its name
+    pub fn new_from_str(name: &str) -> Option<UnixUnit,> {
+        match name {
+            "auto" => Some(UnixUnit::Auto,),
+            "s" => Some(UnixUnit::Seconds,),
+            "ms" => Some(UnixUnit::Millis,),
+            "us" | "\u{b5}s" => Some(UnixUnit::Micros,),
+            "ns" => Some(UnixUnit::Nanos,),
+            _ => None,
+        }
+    }
+
+    /// How many nanoseconds there are in the unit
+    fn nanos(&self) -> i128 {
+        match *self {
+            UnixUnit::Auto | UnixUnit::Seconds => 1_000_000_000,
+            UnixUnit::Millis => 1_000_000,
+            UnixUnit::Micros => 1_000,
+            UnixUnit::Nanos => 1,
+        }
+    }
+}
+
 /// Unix Seconds Clock (*USC*) value
 ///
-/// Optional, unsigned, number of seconds
-/// since 1970-01-01T00:00:00
+/// Optional, signed, number of seconds
+/// since 1970-01-01T00:00:00, and the nanoseconds after
+/// that
 #[derive(Clone, Copy, Debug, Default)]
-pub struct UnixSecondsClock(pub Option<i64,>,);
+pub struct UnixSecondsClock(pub Option<i64,>, pub u32,);
 impl UnixSecondsClock {
     /// Makes a zero USC
-    pub fn new() -> UnixSecondsClock { UnixSecondsClock(None,) }
+    pub fn new() -> UnixSecondsClock { UnixSecondsClock(None, 0,) }
 
     /// Makes a USC from an integer
     pub fn new_from_int(tval: i64) -> UnixSecondsClock {
-        UnixSecondsClock(Some(tval,),)
+        UnixSecondsClock(Some(tval,), 0,)
         }
 
-    /// Makes a USC from a decimal string
-    pub fn new_from_decimal(dec: &str) -> UnixSecondsClock {
-        let uval: Result<i64, ParseIntError> = dec.parse();
-        match uval {
-            Ok(n,) => UnixSecondsClock(Some(n,),),
-            _ => UnixSecondsClock(None,),
-            }
+    /// Makes a USC from a decimal string, in the given unit,
+    /// with or without a fraction
+    pub fn new_from_decimal(dec: &str, unit: UnixUnit,) -> UnixSecondsClock {
+        match UnixSecondsClock::nanos_from_decimal(dec, unit,) {
+            Some(n,) if n.div_euclid(1_000_000_000,) <= i128::from(i64::MAX,) && n.div_euclid(1_000_000_000,) >= i128::from(i64::MIN,) => {
+                UnixSecondsClock(Some(n.div_euclid(1_000_000_000,) as i64,), n.rem_euclid(1_000_000_000,) as u32,)
+            },
+            _ => UnixSecondsClock(None, 0,),
+        }
+    }
+
+    /// Finds the nanoseconds in a decimal string
+    fn nanos_from_decimal(dec: &str, unit: UnixUnit,) -> Option<i128,> {
+        let (negative, dec,) = match dec.strip_prefix('-',) {
+            Some(x,) => (true, x,),
+            None => (false, dec,),
+        };
+        let mut parts = dec.splitn(2, '.',);
+        let whole = parts.next()?;
+        let fraction = parts.next().unwrap_or("",);
+        if whole.is_empty() || !whole.bytes().chain(fraction.bytes(),).all(|b| b.is_ascii_digit(),) {
+            return None;
         }
+        let unit = match unit {
+            UnixUnit::Auto => match whole.len() {
+                0..=11 => UnixUnit::Seconds,
+                12..=14 => UnixUnit::Millis,
+                15..=17 => UnixUnit::Micros,
+                _ => UnixUnit::Nanos,
+            },
+            x => x,
+        };
+        let fraction: i128 = format!("{:0<9}", fraction)[..9].parse().ok()?;
+        let n = whole.parse::<i128>().ok()?.checked_mul(unit.nanos(),)? + fraction * unit.nanos() / 1_000_000_000;
+        Some(if negative { -n } else { n },)
+    }
+
+    /// Formats the value in a unit: for *Auto*, whole
+    /// seconds unless there's a fraction
+    pub fn text(&self, unit: UnixUnit,) -> String {
+        let seconds = match self.0 {
+            Some(x,) => x,
+            None => return "--".to_string(),
+        };
+        let n = i128::from(seconds,) * 1_000_000_000 + i128::from(self.1,);
+        match unit {
+            UnixUnit::Auto if n.rem_euclid(1_000_000_000,) < 1_000 => format!("{}", seconds),
+            UnixUnit::Auto | UnixUnit::Seconds => {
+                let micros = n.div_euclid(1_000,);
+                let sign = if micros < 0 { "-" } else { "" };
+                format!("{}{}.{:06}", sign, (micros / 1_000_000).abs(), (micros % 1_000_000).abs())
+            },
+            x => format!("{}", n.div_euclid(x.nanos(),)),
+        }
+    }
 }
 
 impl fmt::Display for UnixSecondsClock {
-    /// Displays as decimal digits
+    /// Displays as decimal digits, whole seconds
     fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
         match self.0 {
             Some(x,) => write!(f, "{:14}", x),
@@ -316,12 +597,18 @@ impl Tod {
     pub fn new(tval: u64) -> Tod { Tod(tval,) }
 
     /// Makes a new clock from a hex string
+    ///
+    /// A string of exactly 32 digits is taken as a full
+    /// STCKE value, regardless of padding
     pub fn new_from_hex(hex: &str, pad: &Padding,) -> Option<Tod,> {
-        if hex.bytes().all(|b| b.is_ascii_hexdigit(),) {
+        if hex.len() == 32 {
+            return Tod::new_from_stcke_hex(hex,);
+        }
+        if !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit(),) {
             let chex = match *pad {
                 Padding::Left => ["000000000000000", hex].join("",)[hex.len()..].to_string(),
                 Padding::Right => [hex, "000000000000000"].join("",)[..16].to_string(),
-                _ => if &hex.to_uppercase().as_bytes()[..1] > b"B" {
+                _ => if hex.to_uppercase().as_bytes()[..1] > b"B"[..] {
                     ["000", hex, "000000000000"].join("",)[..16].to_string()
                 } else {
                     ["00", hex, "0000000000000"].join("",)[..16].to_string()
@@ -336,6 +623,34 @@ impl Tod {
             None
         }
     }
+
+    /// Makes a new clock from the 32 hex digits of a
+    /// STCKE value: epoch index, TOD bits 0-63, and
+    /// the remaining (ignored) bits
+    pub fn new_from_stcke_hex(hex: &str) -> Option<Tod,> {
+        if hex.len() == 32 && hex.bytes().all(|b| b.is_ascii_hexdigit(),) {
+            u64::from_str_radix(&hex[..15], 16,).ok().map(Tod,)
+        } else {
+            None
+        }
+    }
+
+    /// Makes a new clock from a raw, big-endian, STCK (8
+    /// bytes) or STCKE (16 bytes) value
+    pub fn new_from_bytes(raw: &[u8]) -> Option<Tod,> {
+        let mut x = [0u8; 8];
+        match raw.len() {
+            8 => {
+                x.copy_from_slice(raw,);
+                Some(Tod(u64::from_be_bytes(x,) >> 12,),)
+            },
+            16 => {
+                x.copy_from_slice(&raw[..8],);
+                Some(Tod(u64::from_be_bytes(x,) >> 4,),)
+            },
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Tod {
@@ -359,8 +674,13 @@ impl fmt::Display for Tod {
 /// If a time is specified,
 /// it should be separated from the date by an "@"
 /// character
+///
+/// A Db2 TIMESTAMP (*yyyy-mm-dd-hh.mm.ss.nnnnnn*), or the
+/// start of one, is accepted too
 pub fn finddate(ds: String) -> ParseResult<NaiveDateTime,> {
-    if ds.to_uppercase() == "NOW" {
+    if ds.len() > 10 && ds.as_bytes()[10] == b'-' && ds.is_char_boundary(11,) {
+        finddate(format!("{}@{}", &ds[..10], ds[11..].replacen('.', ":", 2,)),)
+    } else if ds.to_uppercase() == "NOW" {
         NaiveDateTime::parse_from_str(&defaultdate(), "%F@%H:%M:%S%.f",)
     } else {
         let xlen = ds.len();
@@ -384,28 +704,36 @@ pub fn defaultdate() -> String { Utc::now().format("%F@%H:%M:%S%.6f",).to_string
 
 /// Uses a TOD Clock value to calculate the others,
 ///  with up to three different yime zone offsets
-pub fn from_tod(a: &str, todwork: &mut TodInfo,) -> Vec<String,> {
-    let todbase = NaiveDate::from_ymd(1900, 1, 1,).and_hms(0, 0, 0,);
-    let mut result: Vec<String,> = Vec::new();
-    let xtod = Tod::new_from_hex(&a.to_string(), &todwork.pad,);
+///
+/// Gives one line for each time zone, or a description of
+/// why the value can't be converted
+pub fn from_tod(a: &str, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    let xtod = Tod::new_from_hex(a, &todwork.pad,);
     todwork.tod = match xtod {
         None => {
-            result.push(format!("TOD value is invalid: {:?}", a),);
-            return result;
+            return Err(format!("TOD value is invalid: {:?}", a),);
         },
         Some(x,) => x,
     };
+    from_tod_value(todwork,)
+}
+
+/// Uses the TOD Clock value already in the work area to
+/// calculate the others, with up to three different time
+/// zone offsets
+pub fn from_tod_value(todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    let todbase = NaiveDate::from_ymd(1900, 1, 1,).and_hms(0, 0, 0,);
+    let mut result: Vec<String,> = Vec::new();
     todwork.lsec = todwork.lstab.ls_search_tod(todwork,);
     let x = todbase.checked_add_signed(Duration::microseconds(todwork.tod.0 as i64,),);
     let zdate = match x {
         None => {
-            result.push(format!("Can't handle this TOD value: {:?}", a),);
-            return result;
+            return Err(format!("Can't handle this TOD value: {}", todwork.tod),);
         },
         Some(x,) => x,
     };
     let olist = vec![todwork.goff, todwork.loff, todwork.aoff];
-    for off in olist {
+    for (slot, off,) in olist.into_iter().enumerate() {
         match off.0 {
             None => {},
             Some(x,) => {
@@ -414,157 +742,120 @@ pub fn from_tod(a: &str, todwork: &mut TodInfo,) -> Vec<String,> {
                     .expect("Couldn't convert date",);
                 todwork.pmc = findpmc(todwork,);
                 todwork.usc = findusc(todwork,);
-                result.push(todwork.text(off,),);
+                result.push(todwork.text(slot, off,),);
             },
         };
     }
-    result
+    Ok(result,)
 }
 
 /// Uses a date/time value to calculate the others,
 ///  with up to three different yime zone offsets
-pub fn from_datetime(a: &str, todwork: &mut TodInfo,) -> Vec<String,> {
-    let mut result: Vec<String,> = Vec::new();
+pub fn from_datetime(a: &str, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
     let xdt = finddate(a.to_string(),);
     todwork.date = match xdt {
         Err(_,) => {
-            result.push(format!("Date {:?} is invalid", a),);
-            return result;
+            return Err(format!("Date {:?} is invalid", a),);
         },
         Ok(x,) => x,
     };
+    from_datetime_value(todwork,)
+}
+
+/// Uses the date/time value already in the work area to
+/// calculate the others, with up to three different time
+/// zone offsets
+///
+/// The TOD Clock value for the date/time itself is left in
+/// the work area.
+pub fn from_datetime_value(todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    let mut result: Vec<String,> = Vec::new();
     todwork.lsec = todwork.lstab.ls_search_day(todwork,);
     let (zsec, zmic,) = get_sec_mic(todwork,);
     todwork.pmc = findpmc(todwork,);
     todwork.usc = findusc(todwork,);
     let olist = vec![todwork.goff, todwork.loff, todwork.aoff];
-    for off in olist {
+    for (slot, off,) in olist.into_iter().enumerate() {
         match off.0 {
             None => {},
             Some(x,) => {
                 let x = zsec as i64 + x + todwork.lsec + todwork.tai;
                 if x >= 0 {
                     todwork.tod = Tod(x as u64 * 1_000_000 + zmic,);
-                    result.push(todwork.text(off,),);
+                    result.push(todwork.text(slot, off,),);
                 } else {
-                    result.push(format!("Date is out of range: {} {}", a, off),);
-                    return result;
+                    return Err(format!("Date is out of range: {} {}", todwork.date, off),);
                 };
             },
         };
     }
-    result
+    // Leave the TOD for the date as given, not for the last
+    // zone
+    let x = zsec as i64 + todwork.lsec + todwork.tai;
+    if x >= 0 {
+        todwork.tod = Tod(x as u64 * 1_000_000 + zmic,);
+    }
+    Ok(result,)
 }
 
 /// Uses a Perpetial Minute Clock value to calculate the
-/// others,  with up to three different time zone offsets
-pub fn from_perpetual(a: &str, todwork: &mut TodInfo,) -> Vec<String,> {
+/// others, as a date/time value
+pub fn from_perpetual(a: &str, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
     let parsbase = NaiveDate::from_ymd(1966, 1, 3,).and_hms(0, 0, 0,);
-    let mut result: Vec<String,> = Vec::new();
-    todwork.pmc = PerpMinuteClock::new_from_hex(a,);
-    let pmc = match todwork.pmc.0 {
+    let pmc = match PerpMinuteClock::new_from_hex(a,).0 {
         None => {
-            result.push(format!("Minute value is invalid: {:?}", a),);
-            return result;
+            return Err(format!("Minute value is invalid: {:?}", a),);
         },
         Some(x,) => x,
     };
     todwork.date = match parsbase.checked_add_signed(Duration::minutes(i64::from(pmc,),),) {
         None => {
-            result.push(format!("Can't handle this pmc value: {:?}", a),);
-            return result;
+            return Err(format!("Can't handle this pmc value: {:?}", a),);
         },
         Some(x,) => x,
     };
-    todwork.usc = findusc(todwork,);
-    todwork.lsec = todwork.lstab.ls_search_day(todwork,);
-    let (zsec, zmic,) = get_sec_mic(todwork,);
-    let olist = vec![todwork.goff, todwork.loff, todwork.aoff];
-    for off in olist {
-        match off.0 {
-            None => {},
-            Some(x,) => {
-                todwork.tod =
-                    Tod((zsec as i64 + x - todwork.lsec - todwork.tai) as u64 * 1_000_000 + zmic,);
-                result.push(todwork.text(off,),);
-            },
-        };
-    }
-    result
+    from_datetime_value(todwork,)
 }
 
-/// Uses a Unix Seconds Clock value to calculate the
-/// others,  with up to three different time zone offsets
-pub fn from_unix(a: &str, todwork: &mut TodInfo,) -> Vec<String,> {
+/// Uses a Unix Seconds Clock value, in the chosen unit, to
+/// calculate the others, as a date/time value
+///
+/// Unix time has no leap seconds, so it gives the date and
+/// time directly.
+pub fn from_unix(a: &str, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
     let unixbase = NaiveDate::from_ymd(1970, 1, 1,).and_hms(0, 0, 0,);
-    let mut result: Vec<String,> = Vec::new();
-    todwork.usc = UnixSecondsClock::new_from_decimal(a);
-    let tusc = match todwork.usc.0 {
+    let usc = UnixSecondsClock::new_from_decimal(a, todwork.unix,);
+    let tusc = match usc.0 {
         None => {
-            result.push(format!("Seconds value is invalid: {:?}", a),);
-            return result;
+            return Err(format!("Seconds value is invalid: {:?}", a),);
         },
         Some(x,) => x,
     };
-    todwork.date = match unixbase.checked_add_signed(Duration::seconds(i64::from(tusc,),),) {
+    todwork.date = match unixbase
+        .checked_add_signed(Duration::seconds(tusc,),)
+        .and_then(|x| x.checked_add_signed(Duration::nanoseconds(i64::from(usc.1,),),),)
+    {
         None => {
-            result.push(format!("Can't handle this pmc value: {:?}", a),);
-            return result;
+            return Err(format!("Can't handle this Unix value: {:?}", a),);
         },
         Some(x,) => x,
     };
-    todwork.pmc = findpmc(todwork,);
-    todwork.lsec = todwork.lstab.ls_search_day(todwork,);
-    let (zsec, zmic,) = get_sec_mic(todwork,);
-    let olist = vec![todwork.goff, todwork.loff, todwork.aoff];
-    for off in olist {
-        match off.0 {
-            None => {},
-            Some(x,) => {
-                todwork.tod =
-                    Tod((zsec as i64 + x - todwork.lsec - todwork.tai) as u64 * 1_000_000 + zmic,);
-                result.push(todwork.text(off,),);
-            },
-        };
-    }
-    result
+    from_datetime_value(todwork,)
 }
 
-/// Builds a list of values for conversion either from the
-/// command line  or optionally from the clipboard
-pub fn args_or_elsewhere(cmdl: &ArgMatches) -> Vec<String,> {
-    let mut result: Vec<String,> = Vec::new();
-    if cmdl.is_present("clipboard",) {
-        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-        for item in ctx.get_contents().unwrap().split_whitespace() {
-            result.push(item.to_string(),);
-        }
-        return result
-    }
-
-    if cmdl.is_present("infile",) {
-        let filename = match cmdl.value_of("infile",) {
-            Some(fname,) => fname,
-            None => "-",
-            };
-        let mut rdr: Box<dyn io::Read> = if filename == "-" {
-            Box::new(io::stdin())
-            } else {
-            Box::new(File::open(filename).unwrap())
-            };
-        let mut buffer = String::new();
-        rdr.read_to_string(&mut buffer).unwrap();
-        for item in buffer.split_whitespace() {
-            result.push(item.to_string(),);
-            }
-        return result;
-    }
-
-    for item in cmdl.values_of("values",).unwrap() {
-        result.push(item.to_string(),);
-        }
-    return result;
+/// Converts one value according to the calculation type,
+/// giving one line for each time zone or a description of
+/// why the value can't be converted
+pub fn convert(a: &str, todwork: &mut TodInfo,) -> Result<Vec<String,>, String,> {
+    match todwork.runtype {
+        TodCalc::FromTod => from_tod(a, todwork,),
+        TodCalc::FromDateTime => from_datetime(a, todwork,),
+        TodCalc::FromPMC => from_perpetual(a, todwork,),
+        TodCalc::FromUnix => from_unix(a, todwork,),
+        TodCalc::FromPacked => from_packed(a, todwork,),
+        TodCalc::FromClock(x,) => from_clock(a, x, todwork,),
     }
+}
 
 /// Calculates a Perpetual Minute Clock from a date and
 /// time, or *None* if out-of-range
@@ -572,7 +863,7 @@ pub fn findpmc(todwork: &TodInfo) -> PerpMinuteClock {
     let parsbase = NaiveDate::from_ymd(1966, 1, 3,).and_hms(0, 0, 0,);
     let pdiff = todwork.date.signed_duration_since(parsbase,);
     let pmin = pdiff.num_seconds() / 60;
-    if pmin >= 0 && pmin <= i64::from(MAX,) {
+    if pmin >= 0 && pmin <= i64::from(u32::MAX,) {
         PerpMinuteClock(Some(pmin as u32,),)
     } else {
         PerpMinuteClock(None,)
@@ -583,8 +874,13 @@ pub fn findpmc(todwork: &TodInfo) -> PerpMinuteClock {
 /// time, or *None* if out-of-range
 pub fn findusc(todwork: &TodInfo) -> UnixSecondsClock {
     let unixbase = NaiveDate::from_ymd(1970, 1, 1,).and_hms(0, 0, 0,);
-    let usec = todwork.date.signed_duration_since(unixbase,).num_seconds();
-    UnixSecondsClock(Some(usec,),)
+    let udiff = todwork.date.signed_duration_since(unixbase,);
+    let usec = udiff.num_seconds();
+    match (udiff - Duration::seconds(usec,)).num_nanoseconds() {
+        Some(x,) if x < 0 => UnixSecondsClock(Some(usec - 1,), (x + 1_000_000_000) as u32,),
+        Some(x,) => UnixSecondsClock(Some(usec,), x as u32,),
+        None => UnixSecondsClock(Some(usec,), 0,),
+    }
 }
 
 /// Converts date and time into seconds and microseconds of
@@ -599,3 +895,38 @@ pub fn get_sec_mic(todwork: &TodInfo) -> (u64, u64,) {
     let zsec = zsec as u64;
     (zsec, zmic,)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pmc_converts_as_a_date() {
+        let mut todwork = TodInfo::new();
+        todwork.runtype = TodCalc::FromPMC;
+        let x = convert("01994340", &mut todwork,).unwrap();
+        assert!(x[0].starts_with("000 d1e0d681 73cc0--- : 2017-01-01 00:00:00.000000",));
+    }
+
+    #[test]
+    fn csv_columns_are_unpadded() {
+        let mut todwork = TodInfo::new();
+        todwork.date = NaiveDate::from_ymd(1960, 1, 1,).and_hms(0, 0, 0,);
+        todwork.columns = vec![find_format("java",).unwrap(), find_format("pday",).unwrap()];
+        assert!(todwork.text(0, todwork.goff,).ends_with(" -315619200000 ----",));
+        todwork.csv = true;
+        assert!(todwork.text(0, todwork.goff,).ends_with(",-315619200000,NA",));
+    }
+
+    #[test]
+    fn unix_units_in_and_out() {
+        let usc = UnixSecondsClock::new_from_decimal("1483228800123", UnixUnit::Auto,);
+        assert_eq!(usc.text(UnixUnit::Auto,), "1483228800.123000");
+        assert_eq!(usc.text(UnixUnit::Millis,), "1483228800123");
+        let usc = UnixSecondsClock::new_from_decimal("1483228800", UnixUnit::Auto,);
+        assert_eq!(usc.text(UnixUnit::Auto,), "1483228800");
+        assert_eq!(usc.text(UnixUnit::Seconds,), "1483228800.000000");
+        let usc = UnixSecondsClock::new_from_decimal("1483228800", UnixUnit::Millis,);
+        assert_eq!(usc.text(UnixUnit::Auto,), "1483228.800000");
+    }
+}
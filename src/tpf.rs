@@ -0,0 +1,136 @@
+extern crate chrono;
+use self::chrono::{NaiveDate, NaiveDateTime};
+
+use super::clockfmt::*;
+
+/// Defines the z/TPF clocks, which all count from the start
+/// of the Perpetual Minute Clock, 1966-01-03 00:00:00, in
+/// local time and without leap seconds:
+/// *   Pday: Perpetual Day, a halfword count of days, the
+///     PARS day number
+/// *   ParsDate: PARS date, a fullword, the Perpetual Day in
+///     the high halfword and the minute of the day in the
+///     low one
+/// *   Psc: Perpetual Second Clock, a fullword count of
+///     seconds, the keypoint second clock (the minute clock
+///     is the PMC itself)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TpfClock {
+    Pday,
+    ParsDate,
+    Psc,
+}
+
+impl ClockFormat for TpfClock {
+    fn name(&self) -> &'static str {
+        match *self {
+            TpfClock::Pday => "pday",
+            TpfClock::ParsDate => "pdate",
+            TpfClock::Psc => "psc",
+        }
+    }
+
+    fn about(&self) -> &'static str {
+        match *self {
+            TpfClock::Pday => "hex z/TPF Perpetual Days (days since 1966-01-03)",
+            TpfClock::ParsDate => "hex 4-byte PARS dates (Perpetual Day, then minute of the day)",
+            TpfClock::Psc => "hex z/TPF Perpetual Second Clock values (seconds since 1966-01-03)",
+        }
+    }
+
+    fn heading(&self) -> &'static str {
+        match *self {
+            TpfClock::Pday => "PDay",
+            TpfClock::ParsDate => "PDate",
+            TpfClock::Psc => "PSC",
+        }
+    }
+
+    fn width(&self) -> usize {
+        match *self {
+            TpfClock::Pday => 4,
+            _ => 8,
+        }
+    }
+
+    fn epoch(&self) -> NaiveDateTime { NaiveDate::from_ymd(1966, 1, 3,).and_hms(0, 0, 0,) }
+
+    /// A PARS date is counted in minutes
+    fn resolution(&self) -> i128 {
+        match *self {
+            TpfClock::Pday => 86_400_000_000_000,
+            TpfClock::ParsDate => 60_000_000_000,
+            TpfClock::Psc => 1_000_000_000,
+        }
+    }
+
+    fn range(&self) -> (i128, i128,) {
+        match *self {
+            TpfClock::Pday => (0, 0xffff,),
+            TpfClock::ParsDate => (0, 0x1_0000 * 1440 - 1,),
+            TpfClock::Psc => (0, 0xffff_ffff,),
+        }
+    }
+
+    /// Refuses a PARS date whose minute is past the end of
+    /// the day
+    fn parse(&self, hex: &str,) -> Option<i128,> {
+        match *self {
+            TpfClock::Pday => hex_value(hex, 4,).map(i128::from,),
+            TpfClock::ParsDate => {
+                let pdate = hex_value(hex, 8,)?;
+                let minute = pdate & 0xffff;
+                if minute >= 1440 { None } else { Some(i128::from(pdate >> 16,) * 1440 + i128::from(minute,),) }
+            },
+            TpfClock::Psc => hex_value(hex, 8,).map(i128::from,),
+        }
+    }
+
+    fn format(&self, count: i128,) -> String {
+        match *self {
+            TpfClock::Pday => format!("{:04x}", count),
+            TpfClock::ParsDate => format!("{:08x}", ((count / 1440) << 16) | (count % 1440)),
+            TpfClock::Psc => format!("{:08x}", count),
+        }
+    }
+
+    fn pattern(&self) -> &'static str {
+        match *self {
+            TpfClock::Pday => r"\b[0-9A-Fa-f]{4}\b",
+            _ => r"\b[0-9A-Fa-f]{8}\b",
+        }
+    }
+
+    /// Reads a field of up to a halfword (Perpetual Day) or
+    /// a fullword
+    fn count_from_bytes(&self, raw: &[u8],) -> Option<i128,> {
+        let hex = hex_string(raw,);
+        self.parse(&hex,)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::todinfo::TodInfo;
+    use super::*;
+
+    const CLOCKS: [(TpfClock, &str,); 3] =
+        [(TpfClock::Pday, "48c2",), (TpfClock::ParsDate, "48c20000",), (TpfClock::Psc, "5febc300",)];
+
+    #[test]
+    fn round_trip_2017() {
+        let mut todwork = TodInfo::new();
+        todwork.date = NaiveDate::from_ymd(2017, 1, 1,).and_hms(0, 0, 0,);
+        for &(clock, text,) in CLOCKS.iter() {
+            assert_eq!(format_value(&clock, &todwork,).as_deref(), Some(text));
+            assert_eq!(clock.parse(text,).and_then(|x| clock.to_instant(x, &todwork,),), Some(todwork.date));
+        }
+    }
+
+    #[test]
+    fn minute_past_the_day_is_refused() {
+        assert_eq!(TpfClock::ParsDate.parse("48c2059f",), Some(18626 * 1440 + 1439));
+        assert_eq!(TpfClock::ParsDate.parse("48c205a0",), None);
+        assert_eq!(TpfClock::ParsDate.count_from_bytes(&[0x48, 0xc2, 0x05, 0xa0],), None);
+    }
+}